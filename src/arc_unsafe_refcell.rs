@@ -1,15 +1,74 @@
 use std::{
     fmt::Display,
     ops::{Deref, DerefMut},
+    panic::Location,
     ptr::NonNull,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+    sync::Mutex,
 };
 
+use pyo3::prelude::*;
+
+// debug_borrows() 打开后记录每个借用的来源
+static DEBUG_BORROWS: AtomicBool = AtomicBool::new(false);
+static NEXT_RECORD: AtomicU64 = AtomicU64::new(0);
+
+/// Turns borrow tracking on or off and returns the previous setting.
+pub(crate) fn set_debug_borrows(enabled: bool) -> bool {
+    DEBUG_BORROWS.swap(enabled, Ordering::AcqRel)
+}
+
+pub(crate) fn debug_borrows_enabled() -> bool {
+    DEBUG_BORROWS.load(Ordering::Acquire)
+}
+
+/// Where an outstanding borrow was taken, recorded while debugging is on.
+#[derive(Clone)]
+pub(crate) struct BorrowRecord {
+    id: u64,
+    pub(crate) mutable: bool,
+    // 被 lend() 临时让出的可变借用
+    pub(crate) lent: bool,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) python_stack: Option<String>,
+}
+
+impl Display for BorrowRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.mutable { "mutable" } else { "shared" };
+        write!(f, "{} borrow acquired at {}", kind, self.location)?;
+        if let Some(python_stack) = &self.python_stack {
+            write!(f, "\nPython stack (most recent call last):\n{}", python_stack.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Borrow counters of one cell and the recorded holders.
+pub(crate) struct BorrowState {
+    pub(crate) shared: usize,
+    pub(crate) mutable: usize,
+    pub(crate) holders: Vec<BorrowRecord>,
+}
+
+// 没有 Python 栈时（例如解释器正在退出）返回 None
+fn python_stack() -> Option<String> {
+    Python::with_gil(|py| {
+        let frames = py.import("traceback").ok()?.call_method0("format_stack").ok()?;
+        Some(frames.extract::<Vec<String>>().ok()?.concat())
+    })
+}
+
 pub struct Inner<T> {
     data: *mut T,
+    // lend() 期间指向借出方的可变借用，新的借用都经由它访问数据
+    lent: AtomicPtr<T>,
     ref_count: AtomicUsize,
     borrow_count: AtomicUsize,
     borrow_mut_count: AtomicUsize,
+    // 数据释放后调用，参数为 Inner 的地址
+    on_drop: Option<fn(usize)>,
+    holders: Mutex<Vec<BorrowRecord>>,
 }
 
 impl<T> Inner<T> {
@@ -17,9 +76,81 @@ impl<T> Inner<T> {
         let data = Box::into_raw(Box::new(data));
         Self {
             data,
+            lent: AtomicPtr::new(std::ptr::null_mut()),
             ref_count: AtomicUsize::new(1),
             borrow_count: AtomicUsize::new(0),
             borrow_mut_count: AtomicUsize::new(0),
+            on_drop: None,
+            holders: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn current(&self) -> *mut T {
+        let lent = self.lent.load(Ordering::Acquire);
+        if lent.is_null() {
+            self.data
+        } else {
+            lent
+        }
+    }
+
+    // 在检查借用之前获取 Python 栈，获取过程中可能运行 Python 代码
+    fn prepare_record(mutable: bool, location: &'static Location<'static>) -> Option<BorrowRecord> {
+        if !debug_borrows_enabled() {
+            return None;
+        }
+        Some(BorrowRecord {
+            id: NEXT_RECORD.fetch_add(1, Ordering::Relaxed),
+            mutable,
+            lent: false,
+            location,
+            python_stack: python_stack(),
+        })
+    }
+
+    fn record(&self, record: Option<BorrowRecord>) -> Option<u64> {
+        let record = record?;
+        let id = record.id;
+        self.holders.lock().unwrap_or_else(|e| e.into_inner()).push(record);
+        Some(id)
+    }
+
+    fn forget(&self, id: Option<u64>) {
+        if let Some(id) = id {
+            self.holders
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|record| record.id != id);
+        }
+    }
+
+    // 冲突时列出仍然持有借用的位置；未开启调试时返回 None
+    fn describe_holders(&self, mutable_only: bool) -> Option<String> {
+        if !debug_borrows_enabled() {
+            return None;
+        }
+        let holders = self.holders.lock().unwrap_or_else(|e| e.into_inner());
+        let described: Vec<String> = holders
+            .iter()
+            .filter(|record| !record.lent && (record.mutable || !mutable_only))
+            .map(|record| record.to_string())
+            .collect();
+        if described.is_empty() {
+            Some("a borrow taken before debug_borrows(True) was called".to_string())
+        } else {
+            Some(described.join("\n"))
+        }
+    }
+
+    // lend() 让出最近一次尚未让出的可变借用
+    fn mark_lent(&self, lent: bool) {
+        let mut holders = self.holders.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(record) = holders
+            .iter_mut()
+            .rev()
+            .find(|record| record.mutable && record.lent != lent)
+        {
+            record.lent = lent;
         }
     }
 }
@@ -29,23 +160,40 @@ impl<T> Drop for Inner<T> {
         unsafe {
             drop(Box::from_raw(self.data));
         }
+        if let Some(on_drop) = self.on_drop {
+            on_drop(self as *const Self as usize);
+        }
     }
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) enum ArcUnsafeRefCellError {
-    BorrowError,
-    BorrowMutError,
+    // 开启 debug_borrows 时附带持有者的描述
+    BorrowError(Option<String>),
+    BorrowMutError(Option<String>),
     UnableToDrop,
+    // lend() 收到的不是本单元中的数据
+    NotOwned,
 }
 
 impl Display for ArcUnsafeRefCellError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ArcUnsafeRefCellError::BorrowError => write!(f, "BorrowError"),
-            ArcUnsafeRefCellError::BorrowMutError => write!(f, "BorrowMutError"),
-            ArcUnsafeRefCellError::UnableToDrop => write!(f, "UnableToDrop"),
+        let holders = match self {
+            ArcUnsafeRefCellError::BorrowError(holders) => {
+                write!(f, "BorrowError")?;
+                holders
+            }
+            ArcUnsafeRefCellError::BorrowMutError(holders) => {
+                write!(f, "BorrowMutError")?;
+                holders
+            }
+            ArcUnsafeRefCellError::UnableToDrop => return write!(f, "UnableToDrop"),
+            ArcUnsafeRefCellError::NotOwned => return write!(f, "NotOwned"),
+        };
+        match holders {
+            Some(holders) => write!(f, ", held by:\n{}", holders),
+            None => Ok(()),
         }
     }
 }
@@ -53,6 +201,7 @@ impl Display for ArcUnsafeRefCellError {
 pub(crate) struct UnsafeRef<T> {
     data: *mut T,
     inner: NonNull<Inner<T>>,
+    record: Option<u64>,
 }
 
 impl<T> Deref for UnsafeRef<T> {
@@ -67,6 +216,7 @@ impl<T> Drop for UnsafeRef<T> {
     fn drop(&mut self) {
         unsafe {
             let inner = self.inner.as_ref();
+            inner.forget(self.record);
             inner
                 .borrow_count
                 .fetch_sub(1, Ordering::Release);
@@ -78,6 +228,7 @@ pub(crate) struct UnsafeRefMut<T> {
     data: *mut T,
     drop_mut: bool,
     inner: NonNull<Inner<T>>,
+    record: Option<u64>,
 }
 
 impl<T> Deref for UnsafeRefMut<T> {
@@ -99,6 +250,7 @@ impl<T> Drop for UnsafeRefMut<T> {
         unsafe {
             if self.drop_mut {
                 let inner = self.inner.as_ref();
+                inner.forget(self.record);
                 inner
                     .borrow_mut_count
                     .fetch_sub(1, Ordering::Release);
@@ -123,7 +275,9 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
         Self { data }
     }
 
+    #[track_caller]
     pub fn borrow(&self) -> Result<UnsafeRef<T>, ArcUnsafeRefCellError> {
+        let record = Inner::<T>::prepare_record(false, Location::caller());
         unsafe {
             let inner = self.data.as_ref();
 
@@ -132,7 +286,7 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                 .load(Ordering::Acquire)
                 > 0
             {
-                return Err(ArcUnsafeRefCellError::BorrowError);
+                return Err(ArcUnsafeRefCellError::BorrowError(inner.describe_holders(true)));
             }
 
             inner
@@ -140,8 +294,9 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                 .fetch_add(1, Ordering::Acquire);
 
             Ok(UnsafeRef {
-                data: inner.data,
+                data: inner.current(),
                 inner: self.data,
+                record: inner.record(record),
             })
         }
     }
@@ -154,7 +309,7 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                 .load(Ordering::Acquire)
                 > 0
             {
-                return Err(ArcUnsafeRefCellError::BorrowError);
+                return Err(ArcUnsafeRefCellError::BorrowError(inner.describe_holders(true)));
             }
             inner
                 .ref_count
@@ -177,7 +332,9 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
         }
     }
 
+    #[track_caller]
     pub fn borrow_mut(&self) -> Result<UnsafeRefMut<T>, ArcUnsafeRefCellError> {
+        let record = Inner::<T>::prepare_record(true, Location::caller());
         unsafe {
             let inner = self.data.as_ref();
 
@@ -187,7 +344,7 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                     .load(Ordering::Acquire)
                     > 0
             {
-                return Err(ArcUnsafeRefCellError::BorrowMutError);
+                return Err(ArcUnsafeRefCellError::BorrowMutError(inner.describe_holders(false)));
             }
 
             inner
@@ -195,9 +352,10 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                 .fetch_add(1, Ordering::Acquire);
 
             Ok(UnsafeRefMut {
-                data: inner.data,
+                data: inner.current(),
                 inner: self.data,
                 drop_mut: true,
+                record: inner.record(record),
             })
         }
     }
@@ -227,16 +385,76 @@ impl<T> ArcUnsafeRefCellWrapper<T> {
                     .load(Ordering::Acquire)
                     > 0
             {
-                return Err(ArcUnsafeRefCellError::BorrowMutError);
+                return Err(ArcUnsafeRefCellError::BorrowMutError(inner.describe_holders(false)));
             }
             Ok(UnsafeRefMut {
-                data: inner.data,
+                data: inner.current(),
                 inner: self.data,
                 drop_mut: false,
+                record: None,
             })
         }
     }
 
+    /// Lends `data`, the caller's mutable borrow of this cell's value, to code
+    /// reached from `f`. Borrows taken inside `f` are checked against each
+    /// other as usual and reach the value through `data`, so they reborrow the
+    /// caller's borrow instead of aliasing it. Fails unless `data` is this
+    /// cell's value and that mutable borrow is the only one outstanding.
+    pub fn lend<R>(&self, data: &mut T, f: impl FnOnce() -> R) -> Result<R, ArcUnsafeRefCellError> {
+        struct Restore<'a, T> {
+            inner: &'a Inner<T>,
+            previous: *mut T,
+        }
+
+        impl<T> Drop for Restore<'_, T> {
+            fn drop(&mut self) {
+                self.inner.lent.store(self.previous, Ordering::Release);
+                self.inner.borrow_mut_count.fetch_add(1, Ordering::Acquire);
+                self.inner.mark_lent(false);
+            }
+        }
+
+        let inner = unsafe { self.data.as_ref() };
+        if !std::ptr::eq(data, inner.data) {
+            return Err(ArcUnsafeRefCellError::NotOwned);
+        }
+        if inner.borrow_count.load(Ordering::Acquire) > 0
+            || inner.borrow_mut_count.load(Ordering::Acquire) != 1
+        {
+            return Err(ArcUnsafeRefCellError::BorrowMutError(inner.describe_holders(false)));
+        }
+        inner.borrow_mut_count.fetch_sub(1, Ordering::Release);
+        inner.mark_lent(true);
+        let previous = inner.lent.swap(data, Ordering::AcqRel);
+        // f 出错展开时同样收回借用
+        let _restore = Restore { inner, previous };
+        Ok(f())
+    }
+
+    /// Registers a function called with the `get_inner()` address once the
+    /// last reference is dropped and the data has been freed.
+    pub fn set_on_drop(&self, on_drop: fn(usize)) {
+        unsafe {
+            (*self.data.as_ptr()).on_drop = Some(on_drop);
+        }
+    }
+
+    /// Current borrow counters and, while debugging, who holds them.
+    pub(crate) fn borrow_state(&self) -> BorrowState {
+        let inner = unsafe { self.data.as_ref() };
+        BorrowState {
+            shared: inner.borrow_count.load(Ordering::Acquire),
+            mutable: inner.borrow_mut_count.load(Ordering::Acquire),
+            holders: inner.holders.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        }
+    }
+
+    /// Address of the data, for identity checks; does not borrow it.
+    pub fn data_ptr(&self) -> *const T {
+        unsafe { self.data.as_ref().data }
+    }
+
     pub fn get_inner(&self) -> *mut Inner<T> {
         self.data.as_ptr()
     }
@@ -0,0 +1,129 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+
+use xlang_vm_core::executor::variable::VMBytes as XlangVMBytes;
+use xlang_vm_core::gc::GCRef as XlangGCRef;
+
+use crate::object_id;
+
+/// Open buffer exports of one bytes object.
+///
+/// Views point straight into the `Vec` owned by the heap object. The VM may
+/// replace that `Vec` when a script reassigns the value, so while a script is
+/// running the exported allocation is taken out of the VM and kept here.
+struct Export {
+    count: usize,
+    // 计数引用，导出期间堆对象不会被回收
+    gc_ref: XlangGCRef,
+    // 脚本执行期间视图所指向的原始存储，虚拟机使用它的副本
+    detached: Option<Vec<u8>>,
+    // 脚本改变了长度、无法放回虚拟机的原始存储，直到最后一个视图释放
+    retired: Vec<Vec<u8>>,
+}
+
+impl Export {
+    fn detach(&mut self) {
+        if self.detached.is_some() {
+            return;
+        }
+        let value = &mut self.gc_ref.as_type::<XlangVMBytes>().value;
+        let copy = value.clone();
+        self.detached = Some(mem::replace(value, copy));
+    }
+
+    fn reattach(&mut self) {
+        let Some(mut original) = self.detached.take() else {
+            return;
+        };
+        let value = &mut self.gc_ref.as_type::<XlangVMBytes>().value;
+        if value.len() == original.len() {
+            // 长度不变时把脚本写入的内容搬回原始存储，视图能看到变化
+            original.copy_from_slice(value);
+            *value = original;
+        } else {
+            self.retired.push(original);
+        }
+    }
+}
+
+thread_local! {
+    // 按堆对象地址记录，同一对象的多个包装共享计数
+    static EXPORTS: RefCell<HashMap<usize, Export>> = RefCell::new(HashMap::new());
+    // 正在执行的 Lambda 调用层数
+    static RUNNING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Registers a new export and returns its id together with the memory the
+/// view should point at.
+pub(crate) fn export(gc_ref: &XlangGCRef) -> (usize, *mut u8, usize) {
+    let id = object_id(gc_ref);
+    let running = RUNNING.with(|running| running.get() > 0);
+    EXPORTS.with(|exports| {
+        let mut exports = exports.borrow_mut();
+        let entry = exports.entry(id).or_insert_with(|| Export {
+            count: 0,
+            gc_ref: gc_ref.clone().clone_ref(),
+            detached: None,
+            retired: Vec::new(),
+        });
+        entry.count += 1;
+        if running {
+            entry.detach();
+        }
+        let data = match entry.detached.as_mut() {
+            Some(original) => original,
+            None => &mut entry.gc_ref.as_type::<XlangVMBytes>().value,
+        };
+        (id, data.as_mut_ptr(), data.len())
+    })
+}
+
+/// Closes one export; the last one frees whatever the views kept alive.
+pub(crate) fn release(id: usize) {
+    let removed = EXPORTS.with(|exports| {
+        let mut exports = exports.borrow_mut();
+        let entry = exports.get_mut(&id)?;
+        entry.count -= 1;
+        if entry.count > 0 {
+            return None;
+        }
+        exports.remove(&id)
+    });
+    // 虚拟机已经持有副本，原始存储随 entry 一起释放
+    if let Some(mut entry) = removed {
+        entry.gc_ref.drop_ref();
+    }
+}
+
+pub(crate) fn is_exported(gc_ref: &XlangGCRef) -> bool {
+    let id = object_id(gc_ref);
+    EXPORTS.with(|exports| exports.borrow().contains_key(&id))
+}
+
+/// Marks a Lambda call. Exported storage is moved out of the VM for the
+/// outermost call and handed back when it ends.
+pub(crate) fn enter_call() -> RunGuard {
+    let depth = RUNNING.with(|running| {
+        running.set(running.get() + 1);
+        running.get()
+    });
+    if depth == 1 {
+        EXPORTS.with(|exports| exports.borrow_mut().values_mut().for_each(Export::detach));
+    }
+    RunGuard
+}
+
+pub(crate) struct RunGuard;
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        let depth = RUNNING.with(|running| {
+            running.set(running.get() - 1);
+            running.get()
+        });
+        if depth == 0 {
+            EXPORTS.with(|exports| exports.borrow_mut().values_mut().for_each(Export::reattach));
+        }
+    }
+}
@@ -0,0 +1,903 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem::size_of;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use xlang_vm_core::executor::variable::{
+    VMBoolean as XlangVMBoolean, VMBytes as XlangVMBytes,
+    VMCLambdaInstruction as XlangVMCLambdaInstruction, VMFloat as XlangVMFloat,
+    VMInstructions as XlangVMInstructions, VMInt as XlangVMInt, VMKeyVal as XlangVMKeyVal,
+    VMLambda as XlangVMLambda, VMNamed as XlangVMNamed, VMNull as XlangVMNull,
+    VMRange as XlangVMRange, VMSet as XlangVMSet, VMString as XlangVMString,
+    VMTuple as XlangVMTuple, VMWrapper as XlangVMWrapper,
+};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+use crate::arc_unsafe_refcell::{ArcUnsafeRefCellWrapper, Inner};
+use crate::lifetime::ScopeFrame;
+use crate::{
+    borrow_error, extract_xlang_gc_ref, object_id, vm_repr, vm_type_name, xlang_gc_ref_to_py_object,
+    CrossHeapError, XlangLeakError,
+};
+
+/// What caused a collection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Trigger {
+    Manual,
+    Allocation,
+    Call,
+    Step,
+}
+
+impl Trigger {
+    pub(crate) const ALL: [Trigger; 4] = [
+        Trigger::Manual,
+        Trigger::Allocation,
+        Trigger::Call,
+        Trigger::Step,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Trigger::Manual => "manual",
+            Trigger::Allocation => "allocation",
+            Trigger::Call => "call",
+            Trigger::Step => "step",
+        }
+    }
+}
+
+/// Collection counters of one heap, shared by every `GCSystem` wrapper of it.
+#[derive(Clone, Default)]
+pub(crate) struct HeapStats {
+    pub(crate) collections: usize,
+    pub(crate) last_freed: usize,
+    pub(crate) total_freed: usize,
+    pub(crate) collect_time: Duration,
+    pub(crate) by_trigger: [usize; 4],
+    pub(crate) last_trigger: Option<Trigger>,
+}
+
+/// When collections run without an explicit `GCSystem.collect()`.
+///
+/// Automatic collections only happen at the boundary of the outermost Lambda
+/// call or between its execution steps, never while a nested call is running.
+#[derive(Clone)]
+pub(crate) struct GcPolicy {
+    // 距上次回收存活对象增长超过该数量时回收
+    pub(crate) allocation_threshold: Option<usize>,
+    pub(crate) collect_after_call: bool,
+    pub(crate) step_interval: Option<usize>,
+}
+
+pub(crate) const DEFAULT_ALLOCATION_THRESHOLD: usize = 10_000;
+
+impl Default for GcPolicy {
+    fn default() -> Self {
+        GcPolicy {
+            allocation_threshold: Some(DEFAULT_ALLOCATION_THRESHOLD),
+            collect_after_call: false,
+            step_interval: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct HeapState {
+    stats: HeapStats,
+    policy: GcPolicy,
+    // paused() 的嵌套层数
+    paused: usize,
+    // 正在执行的 Lambda 调用层数
+    executing: usize,
+    // 执行期间请求的回收，最外层调用结束后进行
+    deferred: Option<Trigger>,
+    steps: usize,
+    // 上次回收后的存活对象数
+    baseline: usize,
+    // 存活的 Python GCSystem 对象数，不包括值对象持有的引用
+    handles: usize,
+    warn_on_leak: bool,
+    // 当前打开的 scope()，最内层在末尾
+    scopes: Vec<Rc<ScopeFrame>>,
+    // weak() 句柄按对象地址共享的存活标记
+    weak: HashMap<usize, Rc<Cell<bool>>>,
+    // 包装为 xlang 函数的 Python 可调用对象，按编号保存
+    callables: HashMap<usize, WrappedCallable>,
+    next_callable: usize,
+    // 当前的 HeapRoots 对象（不计数的指针）及其编号
+    roots: Option<(usize, usize)>,
+    // 堆中 XlangGCSystem 的地址，原生函数只拿得到这个地址
+    core: usize,
+}
+
+struct WrappedCallable {
+    callable: PyObject,
+    // 对应的 xlang 函数对象地址，函数对象被回收时释放可调用对象
+    function: Option<usize>,
+}
+
+thread_local! {
+    // 同一个堆可能被多个 Python GCSystem 对象共享，按堆地址记录状态
+    static HEAP_STATE: RefCell<HashMap<usize, HeapState>> = RefCell::new(HashMap::new());
+    static NEXT_ROOTS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn heap_key(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> usize {
+    gc_system.get_inner() as usize
+}
+
+fn with_state<R>(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    f: impl FnOnce(&mut HeapState) -> R,
+) -> R {
+    HEAP_STATE.with(|state| f(state.borrow_mut().entry(heap_key(gc_system)).or_default()))
+}
+
+// 新建堆时调用，堆释放时清除记录
+pub(crate) fn register(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) {
+    let state = HeapState {
+        core: gc_system.data_ptr() as usize,
+        ..HeapState::default()
+    };
+    HEAP_STATE.with(|heaps| heaps.borrow_mut().insert(heap_key(gc_system), state));
+    gc_system.set_on_drop(unregister);
+}
+
+fn unregister(heap: usize) {
+    let state = HEAP_STATE
+        .try_with(|heaps| heaps.try_borrow_mut().ok()?.remove(&heap))
+        .ok()
+        .flatten();
+    // 记录中的 Python 对象在释放借用后再析构，析构过程可能再次访问堆状态
+    drop(state);
+}
+
+/// Runs a full collection and returns the number of freed objects.
+///
+/// While a Lambda call is running, values the VM is working on are not yet
+/// reachable from the heap, so the collection is deferred until the outermost
+/// call returns and 0 is returned.
+pub(crate) fn collect(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    trigger: Trigger,
+) -> PyResult<usize> {
+    let deferred = with_state(gc_system, |state| {
+        if state.executing > 0 {
+            state.deferred.get_or_insert(trigger);
+        }
+        state.executing > 0
+    });
+    if deferred {
+        return Ok(0);
+    }
+    collect_now(gc_system, trigger)
+}
+
+// 只能在没有执行到一半的调用时使用：调用之外，或执行循环的两步之间
+fn collect_now(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    trigger: Trigger,
+) -> PyResult<usize> {
+    let started = Instant::now();
+    let (freed, live, released) = match gc_system.borrow_mut() {
+        Ok(mut heap) => {
+            let before = heap._count();
+            heap.collect();
+            let released = expire(gc_system, &heap);
+            (before - heap._count(), heap._count(), released)
+        }
+        Err(e) => return Err(borrow_error("Unable to collect garbage", e)),
+    };
+    let elapsed = started.elapsed();
+    with_state(gc_system, |state| {
+        let stats = &mut state.stats;
+        stats.collections += 1;
+        stats.last_freed = freed;
+        stats.total_freed += freed;
+        stats.collect_time += elapsed;
+        stats.by_trigger[trigger as usize] += 1;
+        stats.last_trigger = Some(trigger);
+        state.baseline = live;
+        state.steps = 0;
+    });
+    drop(released);
+    Ok(freed)
+}
+
+/// Stores a Python callable for a wrapped function and returns its id.
+///
+/// The entry lives until the function object passed to `bind_callable` is
+/// collected, or until the heap itself is dropped.
+pub(crate) fn add_callable(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    callable: PyObject,
+) -> usize {
+    with_state(gc_system, |state| {
+        let id = state.next_callable;
+        state.next_callable += 1;
+        state.callables.insert(
+            id,
+            WrappedCallable {
+                callable,
+                function: None,
+            },
+        );
+        id
+    })
+}
+
+pub(crate) fn bind_callable(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    id: usize,
+    function: &XlangGCRef,
+) {
+    with_state(gc_system, |state| {
+        if let Some(entry) = state.callables.get_mut(&id) {
+            entry.function = Some(object_id(function));
+        }
+    });
+}
+
+// 创建函数对象失败时调用
+pub(crate) fn remove_callable(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, id: usize) {
+    let entry = with_state(gc_system, |state| state.callables.remove(&id));
+    drop(entry);
+}
+
+/// Looks up the heap running a native function and the callable stored
+/// under `id`.
+///
+/// Returns `None` for the callable once `HeapRoots` has been cleared by
+/// CPython's cycle collector.
+pub(crate) fn callable(
+    core: &XlangGCSystem,
+    id: usize,
+    py: Python,
+) -> Option<(ArcUnsafeRefCellWrapper<XlangGCSystem>, Option<PyObject>)> {
+    let core = core as *const XlangGCSystem as usize;
+    HEAP_STATE.with(|heaps| {
+        let heaps = heaps.borrow();
+        let (heap, state) = heaps.iter().find(|(_, state)| state.core == core)?;
+        let callable = state
+            .callables
+            .get(&id)
+            .map(|entry| entry.callable.clone_ref(py));
+        // 堆正在执行这个函数，必然仍然存活
+        let gc_system = ArcUnsafeRefCellWrapper::from_inner(*heap as *mut Inner<XlangGCSystem>);
+        Some((gc_system, callable))
+    })
+}
+
+/// The `HeapRoots` object of a heap, created on first use.
+fn roots(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    py: Python,
+) -> PyResult<Py<HeapRoots>> {
+    if let Some((pointer, _)) = with_state(gc_system, |state| state.roots) {
+        // 指针在 HeapRoots 析构时清除，这里仍然有效
+        let roots = unsafe { Bound::from_borrowed_ptr(py, pointer as *mut pyo3::ffi::PyObject) };
+        return Ok(roots.downcast_into::<HeapRoots>()?.unbind());
+    }
+    // 创建 Python 对象可能触发 CPython 的回收，不能在借用堆状态时进行
+    let generation = NEXT_ROOTS.with(|next| {
+        next.set(next.get() + 1);
+        next.get()
+    });
+    let roots = Py::new(
+        py,
+        HeapRoots {
+            heap: heap_key(gc_system),
+            generation,
+        },
+    )?;
+    with_state(gc_system, |state| {
+        state.roots = Some((roots.as_ptr() as usize, generation))
+    });
+    Ok(roots)
+}
+
+// 包装对象构造时调用；创建失败时不参与 CPython 的循环回收，只是无法回收跨堆循环
+pub(crate) fn roots_for_wrapper(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+) -> Option<Py<HeapRoots>> {
+    Python::with_gil(|py| roots(gc_system, py).ok())
+}
+
+/// Owner, as far as CPython's cycle collector can tell, of the Python objects
+/// kept alive by one heap.
+///
+/// Every `GCSystem` and `VMValue` wrapper of the heap references it, so a
+/// wrapped callable whose closure references the heap forms a cycle CPython
+/// can see. Clearing it drops the callables; calling their xlang functions
+/// afterwards fails instead of reaching freed objects.
+#[pyclass(unsendable)]
+pub struct HeapRoots {
+    heap: usize,
+    generation: usize,
+}
+
+impl HeapRoots {
+    // 堆释放后地址可能被新堆复用，旧对象不再代表新堆
+    fn is_current(&self, state: &HeapState) -> bool {
+        matches!(state.roots, Some((_, generation)) if generation == self.generation)
+    }
+}
+
+#[pymethods]
+impl HeapRoots {
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        HEAP_STATE.with(|state| {
+            // 回收可能发生在堆状态被借用期间，此时不报告引用，对象被视为仍可达
+            let state = match state.try_borrow() {
+                Ok(state) => state,
+                Err(_) => return Ok(()),
+            };
+            if let Some(state) = state.get(&self.heap) {
+                if !self.is_current(state) {
+                    return Ok(());
+                }
+                for entry in state.callables.values() {
+                    visit.call(&entry.callable)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn __clear__(&mut self) {
+        let callables = HEAP_STATE.with(|state| {
+            state
+                .try_borrow_mut()
+                .ok()?
+                .get_mut(&self.heap)
+                .filter(|state| self.is_current(state))
+                .map(|state| std::mem::take(&mut state.callables))
+        });
+        drop(callables);
+    }
+}
+
+impl Drop for HeapRoots {
+    fn drop(&mut self) {
+        let _ = HEAP_STATE.try_with(|state| {
+            if let Some(state) = state.borrow_mut().get_mut(&self.heap) {
+                if self.is_current(state) {
+                    state.roots = None;
+                }
+            }
+        });
+    }
+}
+
+// 回收后把已释放对象的 weak() 句柄标记为失效（地址之后可能被新对象复用），
+// 并取出函数对象已被回收的 Python 可调用对象，由调用方在释放借用后析构
+fn expire(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, heap: &XlangGCSystem) -> Vec<PyObject> {
+    with_state(gc_system, |state| {
+        if state.weak.is_empty() && state.callables.is_empty() {
+            return Vec::new();
+        }
+        let live: HashSet<usize> = heap._get_all_objects().iter().map(object_id).collect();
+        state.weak.retain(|address, alive| {
+            if !live.contains(address) {
+                alive.set(false);
+                return false;
+            }
+            // 没有句柄再使用的标记也一并移除
+            Rc::strong_count(alive) > 1
+        });
+        let collected: Vec<usize> = state
+            .callables
+            .iter()
+            .filter(|(_, entry)| matches!(entry.function, Some(function) if !live.contains(&function)))
+            .map(|(id, _)| *id)
+            .collect();
+        collected
+            .into_iter()
+            .filter_map(|id| state.callables.remove(&id))
+            .map(|entry| entry.callable)
+            .collect()
+    })
+}
+
+pub(crate) fn stats(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> HeapStats {
+    with_state(gc_system, |state| state.stats.clone())
+}
+
+pub(crate) fn policy(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> (GcPolicy, bool) {
+    with_state(gc_system, |state| (state.policy.clone(), state.paused == 0))
+}
+
+pub(crate) fn set_policy(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, policy: GcPolicy) {
+    with_state(gc_system, |state| {
+        state.policy = policy;
+        state.steps = 0;
+    });
+}
+
+pub(crate) fn pause(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) {
+    with_state(gc_system, |state| state.paused += 1);
+}
+
+pub(crate) fn resume(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) {
+    with_state(gc_system, |state| state.paused = state.paused.saturating_sub(1));
+}
+
+pub(crate) fn current_scope(
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+) -> Option<Rc<ScopeFrame>> {
+    with_state(gc_system, |state| state.scopes.last().cloned())
+}
+
+pub(crate) fn push_scope(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, frame: Rc<ScopeFrame>) {
+    with_state(gc_system, |state| state.scopes.push(frame));
+}
+
+// 作用域不一定按嵌套顺序退出
+pub(crate) fn remove_scope(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, frame: &Rc<ScopeFrame>) {
+    with_state(gc_system, |state| {
+        state.scopes.retain(|scope| !Rc::ptr_eq(scope, frame))
+    });
+}
+
+pub(crate) fn add_handle(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) {
+    with_state(gc_system, |state| state.handles += 1);
+}
+
+// 最后一个 GCSystem 对象被释放并且开启了泄漏警告时返回 true
+pub(crate) fn remove_handle(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> bool {
+    with_state(gc_system, |state| {
+        state.handles = state.handles.saturating_sub(1);
+        state.handles == 0 && state.warn_on_leak
+    })
+}
+
+pub(crate) fn warn_on_leak(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> bool {
+    with_state(gc_system, |state| state.warn_on_leak)
+}
+
+pub(crate) fn set_warn_on_leak(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, warn: bool) {
+    with_state(gc_system, |state| state.warn_on_leak = warn);
+}
+
+/// Counts per VM type of the objects still referenced from Python wrappers.
+pub(crate) fn wrapped_objects(gc_system: &XlangGCSystem) -> BTreeMap<&'static str, usize> {
+    let mut wrapped = BTreeMap::new();
+    for gc_ref in gc_system._get_all_objects() {
+        if gc_ref.get_const_traceable().native_gcref_object_count > 0 {
+            *wrapped.entry(census_type_name(gc_ref)).or_insert(0) += 1;
+        }
+    }
+    wrapped
+}
+
+// 例如 "string x2, tuple x1"
+pub(crate) fn format_type_counts(counts: &BTreeMap<&'static str, usize>) -> String {
+    counts
+        .iter()
+        .map(|(type_name, count)| format!("{} x{}", type_name, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn allocation_exceeded(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, state: &HeapState) -> bool {
+    match state.policy.allocation_threshold {
+        Some(threshold) => match gc_system.borrow() {
+            Ok(heap) => heap._count().saturating_sub(state.baseline) >= threshold,
+            Err(_) => false,
+        },
+        None => false,
+    }
+}
+
+/// Marks the start of a Lambda call. Dropping the guard ends the call and
+/// applies the after-call part of the policy.
+pub(crate) fn enter_call(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> CallGuard {
+    with_state(gc_system, |state| state.executing += 1);
+    CallGuard {
+        gc_system: gc_system.clone(),
+    }
+}
+
+pub(crate) struct CallGuard {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        let trigger = with_state(&self.gc_system, |state| {
+            state.executing = state.executing.saturating_sub(1);
+            if state.executing > 0 {
+                None
+            } else if let Some(trigger) = state.deferred.take() {
+                Some(trigger)
+            } else if state.paused > 0 {
+                None
+            } else if state.policy.collect_after_call {
+                Some(Trigger::Call)
+            } else if allocation_exceeded(&self.gc_system, state) {
+                Some(Trigger::Allocation)
+            } else {
+                None
+            }
+        });
+        if let Some(trigger) = trigger {
+            // 堆仍被借用时跳过这次自动回收，留到下一次触发
+            let _ = collect(&self.gc_system, trigger);
+        }
+    }
+}
+
+// 在执行循环的每一步之前调用，此时没有执行到一半的指令
+pub(crate) fn step(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) {
+    let trigger = with_state(gc_system, |state| {
+        if state.executing != 1 || state.paused > 0 {
+            return None;
+        }
+        state.steps += 1;
+        match state.policy.step_interval {
+            Some(interval) if state.steps >= interval => Some(Trigger::Step),
+            _ if allocation_exceeded(gc_system, state) => Some(Trigger::Allocation),
+            _ => None,
+        }
+    });
+    if let Some(trigger) = trigger {
+        let _ = collect_now(gc_system, trigger);
+    }
+}
+
+/// Context manager returned by `GCSystem.paused()`.
+#[pyclass(unsendable)]
+pub struct CollectionPause {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    entered: bool,
+}
+
+impl CollectionPause {
+    pub(crate) fn new(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> Self {
+        CollectionPause {
+            gc_system: gc_system.clone(),
+            entered: false,
+        }
+    }
+}
+
+#[pymethods]
+impl CollectionPause {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        if slf.entered {
+            return Err(PyRuntimeError::new_err("paused() context is already active"));
+        }
+        slf.entered = true;
+        pause(&slf.gc_system);
+        Ok(slf)
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        if self.entered {
+            self.entered = false;
+            resume(&self.gc_system);
+        }
+        false
+    }
+}
+
+// vm_type_name 没有覆盖的内部类型也要出现在统计中
+pub(crate) fn census_type_name(gc_ref: &XlangGCRef) -> &'static str {
+    match vm_type_name(gc_ref) {
+        "" if gc_ref.isinstance::<XlangVMCLambdaInstruction>() => "clambda_instruction",
+        "" => "unknown",
+        name => name,
+    }
+}
+
+/// Approximate heap footprint of a single VM object: the object itself plus
+/// buffers it owns directly. Referenced objects are not included.
+pub(crate) fn approximate_size(gc_ref: &XlangGCRef) -> usize {
+    let traceable = gc_ref.get_const_traceable();
+    let references = traceable.references.capacity() * size_of::<(XlangGCRef, usize)>();
+    let object = if gc_ref.isinstance::<XlangVMInt>() {
+        size_of::<XlangVMInt>()
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        size_of::<XlangVMFloat>()
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        size_of::<XlangVMString>() + gc_ref.as_const_type::<XlangVMString>().value.capacity()
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        size_of::<XlangVMBoolean>()
+    } else if gc_ref.isinstance::<XlangVMNull>() {
+        size_of::<XlangVMNull>()
+    } else if gc_ref.isinstance::<XlangVMBytes>() {
+        size_of::<XlangVMBytes>() + gc_ref.as_const_type::<XlangVMBytes>().value.capacity()
+    } else if gc_ref.isinstance::<XlangVMRange>() {
+        size_of::<XlangVMRange>()
+    } else if gc_ref.isinstance::<XlangVMKeyVal>() {
+        size_of::<XlangVMKeyVal>()
+    } else if gc_ref.isinstance::<XlangVMNamed>() {
+        size_of::<XlangVMNamed>()
+    } else if gc_ref.isinstance::<XlangVMTuple>() {
+        size_of::<XlangVMTuple>()
+            + gc_ref.as_const_type::<XlangVMTuple>().values.capacity() * size_of::<XlangGCRef>()
+    } else if gc_ref.isinstance::<XlangVMWrapper>() {
+        size_of::<XlangVMWrapper>()
+    } else if gc_ref.isinstance::<XlangVMSet>() {
+        size_of::<XlangVMSet>()
+    } else if gc_ref.isinstance::<XlangVMLambda>() {
+        size_of::<XlangVMLambda>() + gc_ref.as_const_type::<XlangVMLambda>().signature.capacity()
+    } else if gc_ref.isinstance::<XlangVMInstructions>() {
+        size_of::<XlangVMInstructions>()
+    } else if gc_ref.isinstance::<XlangVMCLambdaInstruction>() {
+        size_of::<XlangVMCLambdaInstruction>()
+    } else {
+        0
+    };
+    object + references
+}
+
+/// Live objects and approximate bytes per VM type.
+pub(crate) fn census(gc_system: &XlangGCSystem) -> BTreeMap<&'static str, (usize, usize)> {
+    let mut census = BTreeMap::new();
+    for gc_ref in gc_system._get_all_objects() {
+        let entry = census.entry(census_type_name(gc_ref)).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += approximate_size(gc_ref);
+    }
+    census
+}
+
+// 以下函数返回的 GCRef 都不计数，只能在下一次回收前使用
+
+/// Heap objects that hold a reference to `target`.
+pub(crate) fn referrers(gc_system: &XlangGCSystem, target: &XlangGCRef) -> Vec<XlangGCRef> {
+    gc_system
+        ._get_all_objects()
+        .iter()
+        .filter(|gc_ref| gc_ref.get_const_traceable().references.contains_key(target))
+        .cloned()
+        .collect()
+}
+
+/// Heap objects that `target` holds a reference to.
+pub(crate) fn referents(target: &XlangGCRef) -> Vec<XlangGCRef> {
+    target
+        .get_const_traceable()
+        .references
+        .keys()
+        .cloned()
+        .collect()
+}
+
+// 没有任何引用、等待下次回收的对象，不能再交给 Python
+fn is_garbage(gc_ref: &XlangGCRef) -> bool {
+    !gc_ref.is_online() && gc_ref.get_const_traceable().ref_count == 0
+}
+
+pub(crate) fn wrap_all(
+    gc_refs: Vec<XlangGCRef>,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    py: Python,
+) -> PyResult<Vec<PyObject>> {
+    gc_refs
+        .into_iter()
+        .filter(|gc_ref| !is_garbage(gc_ref))
+        .map(|mut gc_ref| xlang_gc_ref_to_py_object(&mut gc_ref, gc_system.clone(), py))
+        .collect()
+}
+
+/// The set of objects alive in a heap at one point in time.
+///
+/// Objects are identified by address, so an object freed and replaced by a
+/// new one at the same address between two snapshots is not reported.
+#[pyclass(unsendable)]
+pub struct HeapSnapshot {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    objects: HashSet<usize>,
+}
+
+impl HeapSnapshot {
+    pub(crate) fn take(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> PyResult<Self> {
+        let objects = match gc_system.borrow() {
+            Ok(heap) => heap._get_all_objects().iter().map(object_id).collect(),
+            Err(e) => return Err(borrow_error("Unable to take heap snapshot", e)),
+        };
+        Ok(HeapSnapshot {
+            gc_system: gc_system.clone(),
+            objects,
+        })
+    }
+}
+
+#[pymethods]
+impl HeapSnapshot {
+    fn __len__(&self) -> usize {
+        self.objects.len()
+    }
+
+    fn __contains__(&self, value: &Bound<'_, PyAny>) -> PyResult<bool> {
+        let mut gc_ref = extract_xlang_gc_ref(value)?;
+        let contains = self.objects.contains(&object_id(&gc_ref));
+        gc_ref.drop_ref();
+        Ok(contains)
+    }
+
+    // 返回 other 中有而本快照中没有、并且仍然存活的对象
+    #[pyo3(text_signature = "($self, other)")]
+    fn diff(&self, other: &HeapSnapshot, py: Python) -> PyResult<Vec<PyObject>> {
+        if heap_key(&self.gc_system) != heap_key(&other.gc_system) {
+            return Err(CrossHeapError::new_err(
+                "cannot diff snapshots of different GCSystem heaps",
+            ));
+        }
+        let appeared = match self.gc_system.borrow() {
+            Ok(heap) => heap
+                ._get_all_objects()
+                .iter()
+                .filter(|gc_ref| {
+                    let id = object_id(gc_ref);
+                    other.objects.contains(&id) && !self.objects.contains(&id)
+                })
+                .cloned()
+                .collect(),
+            Err(e) => return Err(borrow_error("Unable to diff heap snapshots", e)),
+        };
+        wrap_all(appeared, &self.gc_system, py)
+    }
+}
+
+/// Weak handle returned by `GCSystem.weak()`.
+///
+/// Does not root the object. Calling the handle returns a new wrapper while
+/// the object is alive and `None` once a collection has freed it.
+#[pyclass(unsendable)]
+pub struct WeakHandle {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    // 不计数的引用，只在 alive 为 true 时使用
+    gc_ref: XlangGCRef,
+    alive: Rc<Cell<bool>>,
+}
+
+impl WeakHandle {
+    pub(crate) fn new(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, gc_ref: &XlangGCRef) -> Self {
+        let alive = with_state(gc_system, |state| {
+            state
+                .weak
+                .entry(object_id(gc_ref))
+                .or_insert_with(|| Rc::new(Cell::new(true)))
+                .clone()
+        });
+        WeakHandle {
+            gc_system: gc_system.clone(),
+            gc_ref: gc_ref.clone(),
+            alive,
+        }
+    }
+}
+
+#[pymethods]
+impl WeakHandle {
+    fn __call__(&self, py: Python) -> PyResult<PyObject> {
+        if !self.alive.get() {
+            return Ok(py.None());
+        }
+        xlang_gc_ref_to_py_object(&mut self.gc_ref.clone(), self.gc_system.clone(), py)
+    }
+
+    #[getter]
+    fn alive(&self) -> bool {
+        self.alive.get()
+    }
+
+    fn __repr__(&self) -> String {
+        if self.alive.get() {
+            format!(
+                "<xlang weak handle to {} at {:#x}>",
+                census_type_name(&self.gc_ref),
+                object_id(&self.gc_ref)
+            )
+        } else {
+            format!("<xlang weak handle at {:#x}; dead>", object_id(&self.gc_ref))
+        }
+    }
+}
+
+// 泄漏报告中最多列出的对象数
+const LEAK_REPORT_LIMIT: usize = 20;
+
+/// Context manager returned by `GCSystem.leak_check()`.
+///
+/// Objects alive on exit, after a full collection, that were not alive on
+/// entry are reported through `XlangLeakError`. The check is skipped when the
+/// block raises.
+#[pyclass(unsendable)]
+pub struct LeakCheck {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    baseline: Option<HashSet<usize>>,
+}
+
+impl LeakCheck {
+    pub(crate) fn new(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> Self {
+        LeakCheck {
+            gc_system: gc_system.clone(),
+            baseline: None,
+        }
+    }
+}
+
+#[pymethods]
+impl LeakCheck {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        if slf.baseline.is_some() {
+            return Err(PyRuntimeError::new_err("leak_check() context is already active"));
+        }
+        // 回收会被推迟，无法在退出时得到准确结果
+        if with_state(&slf.gc_system, |state| state.executing > 0) {
+            return Err(PyRuntimeError::new_err(
+                "leak_check() cannot be used while a Lambda call is running",
+            ));
+        }
+        slf.baseline = Some(HeapSnapshot::take(&slf.gc_system)?.objects);
+        Ok(slf)
+    }
+
+    fn __exit__(
+        &mut self,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        let baseline = match self.baseline.take() {
+            Some(baseline) => baseline,
+            None => return Ok(false),
+        };
+        if !exc_type.is_none() {
+            return Ok(false);
+        }
+        collect(&self.gc_system, Trigger::Manual)?;
+        let report = match self.gc_system.borrow() {
+            Ok(heap) => {
+                let leaked: Vec<&XlangGCRef> = heap
+                    ._get_all_objects()
+                    .iter()
+                    .filter(|gc_ref| !baseline.contains(&object_id(gc_ref)))
+                    .collect();
+                if leaked.is_empty() {
+                    None
+                } else {
+                    let mut counts = BTreeMap::new();
+                    for gc_ref in &leaked {
+                        *counts.entry(census_type_name(gc_ref)).or_insert(0) += 1;
+                    }
+                    let mut report = format!(
+                        "{} VM objects leaked: {}",
+                        leaked.len(),
+                        format_type_counts(&counts)
+                    );
+                    for gc_ref in leaked.iter().take(LEAK_REPORT_LIMIT) {
+                        let repr = vm_repr(gc_ref).unwrap_or_else(|_| "<unprintable>".to_string());
+                        report.push_str(&format!("\n  {}: {}", census_type_name(gc_ref), repr));
+                    }
+                    if leaked.len() > LEAK_REPORT_LIMIT {
+                        report.push_str(&format!(
+                            "\n  ... and {} more",
+                            leaked.len() - LEAK_REPORT_LIMIT
+                        ));
+                    }
+                    Some(report)
+                }
+            }
+            Err(e) => return Err(borrow_error("Unable to check for leaks", e)),
+        };
+        match report {
+            Some(report) => Err(XlangLeakError::new_err(report)),
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use xlang_vm_core::executor::variable::{
+    VMBoolean as XlangVMBoolean, VMBytes as XlangVMBytes, VMFloat as XlangVMFloat,
+    VMInt as XlangVMInt, VMKeyVal as XlangVMKeyVal, VMNamed as XlangVMNamed, VMNull as XlangVMNull,
+    VMString as XlangVMString, VMTuple as XlangVMTuple, VMWrapper as XlangVMWrapper,
+};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+use crate::{new_tuple_from_refs, release_all, vm_type_name};
+
+/// Key of the single-entry object used to carry bytes through JSON:
+/// `{"$bytes": "<standard base64>"}` maps to `VMBytes` in both directions.
+pub(crate) const BYTES_KEY: &str = "$bytes";
+
+// JSON 对象转换成的键值对类型
+#[derive(Clone, Copy)]
+pub(crate) enum ObjectPairs {
+    KeyVal,
+    Named,
+}
+
+impl ObjectPairs {
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "keyval" => Ok(ObjectPairs::KeyVal),
+            "named" => Ok(ObjectPairs::Named),
+            _ => Err(PyValueError::new_err(format!(
+                "object_pairs must be 'keyval' or 'named', not '{}'",
+                name
+            ))),
+        }
+    }
+}
+
+// 保留对象键顺序的 JSON 树，serde_json::Value 默认会按键排序
+enum JsonNode {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonNode>),
+    Object(Vec<(String, JsonNode)>),
+}
+
+impl<'de> Deserialize<'de> for JsonNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(JsonNodeVisitor)
+    }
+}
+
+struct JsonNodeVisitor;
+
+impl<'de> Visitor<'de> for JsonNodeVisitor {
+    type Value = JsonNode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<JsonNode, E> {
+        Ok(JsonNode::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<JsonNode, E> {
+        Ok(JsonNode::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<JsonNode, E> {
+        Ok(JsonNode::Int(value))
+    }
+
+    // 超出 i64 的整数退化为浮点数
+    fn visit_u64<E>(self, value: u64) -> Result<JsonNode, E> {
+        Ok(match i64::try_from(value) {
+            Ok(value) => JsonNode::Int(value),
+            Err(_) => JsonNode::Float(value as f64),
+        })
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<JsonNode, E> {
+        Ok(JsonNode::Float(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<JsonNode, E> {
+        Ok(JsonNode::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<JsonNode, E> {
+        Ok(JsonNode::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonNode, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(JsonNode::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonNode, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(JsonNode::Object(entries))
+    }
+}
+
+struct SortedNode<'a> {
+    node: &'a JsonNode,
+    sort_keys: bool,
+}
+
+impl Serialize for SortedNode<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.node {
+            JsonNode::Null => serializer.serialize_unit(),
+            JsonNode::Bool(value) => serializer.serialize_bool(*value),
+            JsonNode::Int(value) => serializer.serialize_i64(*value),
+            JsonNode::Float(value) => serializer.serialize_f64(*value),
+            JsonNode::String(value) => serializer.serialize_str(value),
+            JsonNode::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for node in values {
+                    seq.serialize_element(&SortedNode {
+                        node,
+                        sort_keys: self.sort_keys,
+                    })?;
+                }
+                seq.end()
+            }
+            JsonNode::Object(entries) => {
+                let mut entries: Vec<&(String, JsonNode)> = entries.iter().collect();
+                if self.sort_keys {
+                    entries.sort_by(|a, b| a.0.cmp(&b.0));
+                }
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, node) in entries {
+                    map.serialize_entry(
+                        key,
+                        &SortedNode {
+                            node,
+                            sort_keys: self.sort_keys,
+                        },
+                    )?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Parses `data` and allocates the resulting value graph in `gc_system`.
+/// Returns a new reference to the root.
+pub(crate) fn from_json(
+    data: &[u8],
+    object_pairs: ObjectPairs,
+    gc_system: &mut XlangGCSystem,
+) -> PyResult<XlangGCRef> {
+    let node: JsonNode = serde_json::from_slice(data)
+        .map_err(|e| PyValueError::new_err(format!("invalid JSON: {}", e)))?;
+    build(&node, object_pairs, gc_system)
+}
+
+fn build(
+    node: &JsonNode,
+    object_pairs: ObjectPairs,
+    gc_system: &mut XlangGCSystem,
+) -> PyResult<XlangGCRef> {
+    Ok(match node {
+        JsonNode::Null => gc_system.new_object(XlangVMNull::new()),
+        JsonNode::Bool(value) => gc_system.new_object(XlangVMBoolean::new(*value)),
+        JsonNode::Int(value) => gc_system.new_object(XlangVMInt::new(*value)),
+        JsonNode::Float(value) => gc_system.new_object(XlangVMFloat::new(*value)),
+        JsonNode::String(value) => gc_system.new_object(XlangVMString::new(value)),
+        JsonNode::Object(entries) if is_bytes_object(entries) => {
+            let JsonNode::String(encoded) = &entries[0].1 else {
+                unreachable!()
+            };
+            let value = BASE64.decode(encoded).map_err(|e| {
+                PyValueError::new_err(format!("invalid base64 in '{}': {}", BYTES_KEY, e))
+            })?;
+            gc_system.new_object(XlangVMBytes::new(&value))
+        }
+        JsonNode::Array(values) => {
+            let mut items = Vec::with_capacity(values.len());
+            for value in values {
+                match build(value, object_pairs, gc_system) {
+                    Ok(item) => items.push(item),
+                    Err(e) => return Err(release_all(items, e)),
+                }
+            }
+            new_tuple_from_refs(items, gc_system)
+        }
+        JsonNode::Object(entries) => {
+            let mut items = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let mut value = match build(value, object_pairs, gc_system) {
+                    Ok(value) => value,
+                    Err(e) => return Err(release_all(items, e)),
+                };
+                let mut key = gc_system.new_object(XlangVMString::new(key));
+                let item = match object_pairs {
+                    ObjectPairs::KeyVal => {
+                        gc_system.new_object(XlangVMKeyVal::new(&mut key, &mut value))
+                    }
+                    ObjectPairs::Named => {
+                        gc_system.new_object(XlangVMNamed::new(&mut key, &mut value))
+                    }
+                };
+                key.drop_ref();
+                value.drop_ref();
+                items.push(item);
+            }
+            new_tuple_from_refs(items, gc_system)
+        }
+    })
+}
+
+fn is_bytes_object(entries: &[(String, JsonNode)]) -> bool {
+    entries.len() == 1 && entries[0].0 == BYTES_KEY && matches!(entries[0].1, JsonNode::String(_))
+}
+
+/// Serializes the value graph rooted at `gc_ref`.
+/// Tuples made only of string-keyed `VMKeyVal`/`VMNamed` pairs become objects.
+pub(crate) fn to_json(
+    gc_ref: &XlangGCRef,
+    indent: Option<usize>,
+    sort_keys: bool,
+) -> PyResult<String> {
+    let node = to_node(gc_ref, &mut HashSet::new())?;
+    let sorted = SortedNode {
+        node: &node,
+        sort_keys,
+    };
+    let result = match indent {
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut output = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+            sorted
+                .serialize(&mut serializer)
+                .map(|_| String::from_utf8(output).expect("serde_json emits UTF-8"))
+        }
+        None => serde_json::to_string(&sorted),
+    };
+    result.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn to_node(gc_ref: &XlangGCRef, visiting: &mut HashSet<XlangGCRef>) -> PyResult<JsonNode> {
+    if !visiting.insert(gc_ref.clone()) {
+        return Err(PyValueError::new_err(
+            "cyclic VM value graphs cannot be serialized to JSON",
+        ));
+    }
+    let node = if gc_ref.isinstance::<XlangVMNull>() {
+        JsonNode::Null
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        JsonNode::Bool(gc_ref.as_const_type::<XlangVMBoolean>().value)
+    } else if gc_ref.isinstance::<XlangVMInt>() {
+        JsonNode::Int(gc_ref.as_const_type::<XlangVMInt>().value)
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        let value = gc_ref.as_const_type::<XlangVMFloat>().value;
+        if !value.is_finite() {
+            return Err(PyValueError::new_err(format!(
+                "float value {} is not JSON compliant",
+                value
+            )));
+        }
+        JsonNode::Float(value)
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        JsonNode::String(gc_ref.as_const_type::<XlangVMString>().value.clone())
+    } else if gc_ref.isinstance::<XlangVMBytes>() {
+        let encoded = BASE64.encode(&gc_ref.as_const_type::<XlangVMBytes>().value);
+        JsonNode::Object(vec![(BYTES_KEY.to_string(), JsonNode::String(encoded))])
+    } else if gc_ref.isinstance::<XlangVMWrapper>() {
+        to_node(
+            &gc_ref.as_const_type::<XlangVMWrapper>().value_ref,
+            visiting,
+        )?
+    } else if gc_ref.isinstance::<XlangVMTuple>() {
+        let values = &gc_ref.as_const_type::<XlangVMTuple>().values;
+        if !values.is_empty() && values.iter().all(|value| string_pair(value).is_some()) {
+            let mut entries = Vec::with_capacity(values.len());
+            for value in values {
+                let (key, value) = string_pair(value).unwrap();
+                entries.push((key, to_node(value, visiting)?));
+            }
+            JsonNode::Object(entries)
+        } else {
+            let mut nodes = Vec::with_capacity(values.len());
+            for value in values {
+                nodes.push(to_node(value, visiting)?);
+            }
+            JsonNode::Array(nodes)
+        }
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "VM value of type '{}' is not JSON serializable",
+            vm_type_name(gc_ref)
+        )));
+    };
+    visiting.remove(gc_ref);
+    Ok(node)
+}
+
+// 键为字符串的 VMKeyVal 或 VMNamed
+fn string_pair(gc_ref: &XlangGCRef) -> Option<(String, &XlangGCRef)> {
+    let (key, value) = if gc_ref.isinstance::<XlangVMKeyVal>() {
+        let keyval = gc_ref.as_const_type::<XlangVMKeyVal>();
+        (&keyval.key, &keyval.value)
+    } else if gc_ref.isinstance::<XlangVMNamed>() {
+        let named = gc_ref.as_const_type::<XlangVMNamed>();
+        (&named.key, &named.value)
+    } else {
+        return None;
+    };
+    if !key.isinstance::<XlangVMString>() {
+        return None;
+    }
+    Some((key.as_const_type::<XlangVMString>().value.clone(), value))
+}
@@ -1,14 +1,35 @@
-use arc_unsafe_refcell::ArcUnsafeRefCellWrapper;
-use pyo3::types::{PyBytes, PyDict, PyFloat, PyInt, PyList, PyNone, PyString, PyTuple};
-use pyo3::{create_exception, prelude::*};
+use arc_unsafe_refcell::{ArcUnsafeRefCellError, ArcUnsafeRefCellWrapper};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_int, c_void};
+use pyo3::exceptions::{PyBufferError, PyIndexError, PyOverflowError, PyResourceWarning, PyTypeError, PyValueError};
+use pyo3::ffi;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyNone, PySlice, PyString, PyTuple};
+use heap::{CollectionPause, HeapRoots, HeapSnapshot, LeakCheck, WeakHandle};
+use lifetime::{AliasRef, Liveness, Owner, Scope, ScopeFrame};
+use portable::{PortableError, PortableGraph};
+use pyo3::{create_exception, import_exception, prelude::*, PyClassInitializer, PyTraverseError, PyVisit};
 use xlang::{Lambda, WrappedPyFunction};
 use xlang_vm_core::executor::variable::{
-    try_copy_as_vmobject, try_repr_vmobject, try_to_string_vmobject, VMBytes as XlangVMBytes, VMFloat as XlangVMFloat, VMInt as XlangVMInt, VMKeyVal as XlangVMKeyVal, VMNamed as XlangVMNamed, VMNull as XlangVMNull, VMRange as XlangVMRange, VMString as XlangVMString, VMTuple as XlangVMTuple, VMWrapper as XlangVMWrapper
+    try_and_as_vmobject, try_copy_as_vmobject, try_deepcopy_as_vmobject, try_div_as_vmobject,
+    try_get_attr_as_vmobject, try_greater_than_as_vmobject, try_index_of_as_vmobject,
+    try_length_of_as_vmobject, try_less_than_as_vmobject,
+    try_not_as_vmobject, try_or_as_vmobject, try_repr_vmobject,
+    try_to_string_vmobject, try_value_of_as_vmobject, try_xor_as_vmobject, VMBoolean as XlangVMBoolean, VMBytes as XlangVMBytes, VMCLambdaInstruction as XlangVMCLambdaInstruction, VMFloat as XlangVMFloat, VMInstructions as XlangVMInstructions, VMInt as XlangVMInt, VMKeyVal as XlangVMKeyVal, VMLambda as XlangVMLambda, VMNamed as XlangVMNamed, VMNull as XlangVMNull, VMRange as XlangVMRange, VMSet as XlangVMSet, VMString as XlangVMString, VMTuple as XlangVMTuple, VMWrapper as XlangVMWrapper, VMVariableError
 };
 use xlang_vm_core::gc::GCRef as XlangGCRef;
 use xlang_vm_core::gc::GCSystem as XlangGCSystem;
 
 mod arc_unsafe_refcell;
+mod buffer;
+mod heap;
+mod json;
+mod lifetime;
+mod literal;
+mod operators;
+mod portable;
 mod xlang;
 
 // type ArcUnsafeGCWrapper = Arc<RefCell<UnsafeGCWrapper>>;
@@ -16,327 +37,809 @@ mod xlang;
 #[pyclass(unsendable)]
 struct GCSystem {
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    roots: Option<Py<HeapRoots>>,
 }
 
-#[allow(dead_code)]
-trait GCRef {
-    fn get_ref(&self) -> &XlangGCRef;
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef;
-    fn clone_ref(&mut self) -> XlangGCRef;
-    fn drop_ref(&mut self);
-}
-
-#[pyclass(unsendable)]
-#[derive(Clone)]
-struct VMInt {
-    gc_ref: XlangGCRef,
+// 所有 VM 值包装类的公共基类
+// 基类负责释放计数的 GCRef(自己持有或交给 scope)，子类中的 gc_ref 只是同一对象的别名，随 Python 对象一同销毁
+#[pyclass(subclass, unsendable, weakref)]
+pub struct VMValue {
+    gc_ref: Option<XlangGCRef>,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    liveness: Liveness,
+    owner: Owner,
+    // 让 CPython 能看到经由堆中 xlang 函数回到 Python 可调用对象的引用
+    roots: Option<Py<HeapRoots>>,
 }
 
-impl VMInt {
-    fn create(gc: &mut GCSystem, value: i64) -> Self {
-        let gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMInt::new(value)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        VMInt {
+impl VMValue {
+    fn create(
+        gc_ref: Option<XlangGCRef>,
+        liveness: Liveness,
+        gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    ) -> Self {
+        let owner = Owner::adopt(&gc_ref, &liveness, &gc_system);
+        let roots = heap::roots_for_wrapper(&gc_system);
+        VMValue {
             gc_ref,
-            gc_system: gc.gc_system.clone(),
+            gc_system,
+            liveness,
+            owner,
+            roots,
         }
     }
-}
 
-impl GCRef for VMInt {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
+    fn value_ref(&self) -> PyResult<&XlangGCRef> {
+        self.liveness.check()?;
+        self.gc_ref.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyTypeError, _>("VM value is not initialized")
+        })
     }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
+
+    // 替换持有的对象(Lambda.load 等)，释放旧引用；新引用仍由原来的 scope 持有
+    fn replace_ref(&mut self, gc_ref: Option<XlangGCRef>) -> PyResult<()> {
+        self.liveness.check()?;
+        let scope = match &self.owner {
+            Owner::Scope(frame, _) if !frame.is_closed() => Some(frame.clone()),
+            _ => None,
+        };
+        let old_ref = std::mem::replace(&mut self.gc_ref, gc_ref);
+        std::mem::replace(&mut self.owner, Owner::Wrapper).release(old_ref);
+        if let (Some(frame), Some(gc_ref)) = (scope, &self.gc_ref) {
+            self.owner = Owner::Scope(frame.clone(), frame.adopt(gc_ref.clone(), self.liveness.clone()));
+        }
+        Ok(())
     }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
+
+    // keep() 调用：引用从 frame 移交给外层 scope 或包装对象自己
+    pub(crate) fn move_out_of(&mut self, frame: &Rc<ScopeFrame>) -> PyResult<()> {
+        self.liveness.check()?;
+        let index = match &self.owner {
+            Owner::Scope(owner, index) if Rc::ptr_eq(owner, frame) => *index,
+            _ => return Ok(()),
+        };
+        self.owner = match frame.take(index) {
+            Some((gc_ref, liveness)) => match frame.open_parent() {
+                Some(parent) => {
+                    let index = parent.adopt(gc_ref, liveness);
+                    Owner::Scope(parent, index)
+                }
+                None => Owner::Wrapper,
+            },
+            None => Owner::Wrapper,
+        };
+        Ok(())
     }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+}
+
+impl Drop for VMValue {
+    fn drop(&mut self) {
+        if self.liveness.is_released() {
+            return;
+        }
+        let gc_ref = self.gc_ref.take();
+        std::mem::replace(&mut self.owner, Owner::Wrapper).release(gc_ref);
     }
 }
 
+// extends 的类不会自动生成转换，这里统一实现；进入 Python 时引用的所有权转交给 VMValue
+macro_rules! vm_value_subclass {
+    ($($t:ty),+ $(,)?) => {
+        $(
+            impl From<$t> for PyClassInitializer<$t> {
+                fn from(mut value: $t) -> Self {
+                    let (gc_ref, liveness) = value.gc_ref.bind();
+                    let base = VMValue::create(Some(gc_ref), liveness, value.gc_system.clone());
+                    PyClassInitializer::from(base).add_subclass(value)
+                }
+            }
+
+            impl<'py> IntoPyObject<'py> for $t {
+                type Target = $t;
+                type Output = Bound<'py, $t>;
+                type Error = PyErr;
+
+                fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+                    Bound::new(py, self)
+                }
+            }
+        )+
+    };
+}
+
+vm_value_subclass!(
+    VMInt,
+    VMFloat,
+    VMString,
+    VMNull,
+    VMBytes,
+    VMKeyVal,
+    VMNamed,
+    VMTuple,
+    VMWrapper,
+    VMRange,
+    VMBoolean,
+    VMSet,
+    VMInstructions,
+    VMCLambdaInstruction,
+    VMObject,
+);
+
+// #[pymethods] 不会展开其中的宏调用，因此由宏接收整个 impl 块，再追加运算符方法
+macro_rules! vm_pymethods {
+    (
+        binary_ops: [$(($op:ident, $rop:ident, $f:path)),* $(,)?],
+        $(unary_ops: [$(($unary:ident, $unary_f:path)),* $(,)?],)?
+        $(compare_ops: [$(($cmp:ident, $cmp_f:path, $swapped:literal)),* $(,)?],)?
+        impl $t:ident { $($body:tt)* }
+    ) => {
+        #[pymethods]
+        impl $t {
+            $($body)*
+
+            $(
+                fn $op(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+                    operators::binary_op(self.gc_ref.get()?, &self.gc_system, other, $f, false, py)
+                }
+
+                fn $rop(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+                    operators::binary_op(self.gc_ref.get()?, &self.gc_system, other, $f, true, py)
+                }
+            )*
+
+            $($(
+                fn $unary(&self, py: Python) -> PyResult<PyObject> {
+                    operators::unary_op(self.gc_ref.get()?, &self.gc_system, $unary_f, py)
+                }
+            )*)?
+
+            $($(
+                fn $cmp(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+                    operators::compare_op(
+                        self.gc_ref.get()?,
+                        &self.gc_system,
+                        other,
+                        $cmp_f,
+                        $swapped,
+                        py,
+                    )
+                }
+            )*)?
+        }
+    };
+}
+
 #[pymethods]
-impl VMInt {
+impl VMValue {
+    // 供 Python 子类使用：VM 值共享同一个堆对象，其他 Python 对象先转换为 VM 值
     #[new]
     #[pyo3(text_signature = "($cls, gc, value)")]
-    fn new(gc: &mut GCSystem, value: i64) -> Self {
-        VMInt::create(gc, value)
+    fn new(gc: &GCSystem, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let gc_ref = extract_xlang_gc_ref_with_gc_arc(value, gc.gc_system.clone())?;
+        Ok(VMValue::create(Some(gc_ref), Liveness::default(), gc.gc_system.clone()))
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_value(&self) -> i64 {
-        self.gc_ref.as_const_type::<XlangVMInt>().value
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        if let Some(roots) = &self.roots {
+            visit.call(roots)?;
+        }
+        Ok(())
     }
 
-    #[pyo3(text_signature = "($self, value)")]
-    fn set_value(&mut self, value: i64) {
-        self.gc_ref.as_type::<XlangVMInt>().value = value;
+    fn __clear__(&mut self) {
+        self.roots = None;
     }
 
-    fn __repr__(&self) -> PyResult<String> {
-        Ok(format!("VMInt({})", self.get_value()))
+    // 与脚本中 typeof 的结果一致
+    #[getter]
+    fn type_name(&self) -> PyResult<&'static str> {
+        Ok(vm_type_name(self.value_ref()?))
     }
 
-    fn __str__(&self) -> PyResult<String> {
-        Ok(format!("{}", self.get_value()))
+    #[pyo3(text_signature = "($self, py)")]
+    fn to_py(&self, py: Python) -> PyResult<PyObject> {
+        // 有对应 Python 类型的子类会覆盖这里，其余类型原样返回
+        xlang_gc_ref_to_py_object(&mut self.value_ref()?.clone(), self.gc_system.clone(), py)
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn clone(&mut self) -> Self {
-        let value = XlangVMInt::new(self.get_value());
-        let gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(value),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        return VMInt {
-            gc_ref,
-            gc_system: self.gc_system.clone(),
+    // 浅拷贝，容器中的元素与原对象共享
+    #[pyo3(text_signature = "($self, py)")]
+    fn clone(&self, py: Python) -> PyResult<PyObject> {
+        let mut self_ref = self.value_ref()?.clone();
+        let copied = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => try_copy_as_vmobject(&mut self_ref, &mut gc_system),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
+        self.wrap_result(copied, py)
     }
 
     #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&self, py: Python) -> PyResult<PyObject> {
-        let value = self.get_value();
-        let py_int = PyInt::new(py, value);
-        Ok(py_int.into())
+    fn deepcopy(&self, py: Python) -> PyResult<PyObject> {
+        let mut self_ref = self.value_ref()?.clone();
+        let copied = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => try_deepcopy_as_vmobject(&mut self_ref, &mut gc_system),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        self.wrap_result(copied, py)
     }
-}
 
-impl Drop for VMInt {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+    fn __copy__(&self, py: Python) -> PyResult<PyObject> {
+        self.clone(py)
     }
-}
 
-#[pyclass(unsendable)]
-#[derive(Clone)]
-struct VMFloat {
-    gc_ref: XlangGCRef,
-    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
-}
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        self.deepcopy(py)
+    }
 
-impl VMFloat {
-    fn create(gc: &mut GCSystem, value: f64) -> Self {
-        let gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMFloat::new(value)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        VMFloat {
-            gc_ref,
-            gc_system: gc.gc_system.clone(),
+    // 立即释放引用，之后包装对象的任何操作都会抛出 ReferenceError；重复调用无效果
+    #[pyo3(text_signature = "($self)")]
+    fn release(&mut self) {
+        if self.liveness.is_released() {
+            return;
         }
+        let gc_ref = self.gc_ref.take();
+        std::mem::replace(&mut self.owner, Owner::Wrapper).release(gc_ref);
+        self.liveness.release();
     }
-}
 
-impl GCRef for VMFloat {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
+    #[getter]
+    fn released(&self) -> bool {
+        self.liveness.is_released()
     }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
-    }
-}
 
-#[pymethods]
-impl VMFloat {
-    #[new]
-    #[pyo3(text_signature = "($cls, gc, value)")]
-    fn new(gc: &mut GCSystem, value: f64) -> Self {
-        VMFloat::create(gc, value)
+    #[pyo3(text_signature = "($self, other)")]
+    fn same_object(&self, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(is_same_object(self.value_ref()?, other))
     }
 
     #[pyo3(text_signature = "($self)")]
-    fn get_value(&self) -> f64 {
-        self.gc_ref.as_const_type::<XlangVMFloat>().value
+    fn id(&self) -> PyResult<usize> {
+        Ok(object_id(self.value_ref()?))
     }
 
-    #[pyo3(text_signature = "($self, value)")]
-    fn set_value(&mut self, value: f64) {
-        self.gc_ref.as_type::<XlangVMFloat>().value = value;
+    // Python 包装对象持有的引用数加上堆中其他对象持有的引用数
+    #[getter]
+    fn refcount(&self) -> PyResult<usize> {
+        let traceable = self.value_ref()?.get_const_traceable();
+        Ok(traceable.native_gcref_object_count + traceable.ref_count)
     }
 
-    fn __repr__(&self) -> PyResult<String> {
-        Ok(format!("VMFloat({})", self.get_value()))
+    #[getter]
+    fn gc(&self) -> GCSystem {
+        GCSystem::from_heap(self.gc_system.clone())
     }
-    fn __str__(&self) -> PyResult<String> {
-        Ok(format!("{}", self.get_value()))
+
+    // bytes 编码为 {"$bytes": "<base64>"}
+    #[pyo3(signature = (indent=None, sort_keys=false))]
+    fn to_json(&self, indent: Option<usize>, sort_keys: bool) -> PyResult<String> {
+        json::to_json(self.value_ref()?, indent, sort_keys)
     }
 
+    // 输出 xlang 字面量语法，可由 GCSystem.parse_literal 或脚本读回
     #[pyo3(text_signature = "($self)")]
-    fn clone(&mut self) -> Self {
-        let value = XlangVMFloat::new(self.get_value());
-        let gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(value),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        return VMFloat {
-            gc_ref,
-            gc_system: self.gc_system.clone(),
-        };
+    fn to_source(&self) -> PyResult<String> {
+        literal::to_source(self.value_ref()?)
     }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&self, py: Python) -> PyResult<PyObject> {
-        let value = self.get_value();
-        let py_float = PyFloat::new(py, value);
-        Ok(py_float.into())
+    // sys.getsizeof 报告包装对象加上底层 VM 对象的近似大小
+    fn __sizeof__(&self) -> PyResult<usize> {
+        Ok(std::mem::size_of::<Self>() + heap::approximate_size(self.value_ref()?))
+    }
+
+    // 序列化整个值图，在反序列化一方的默认 GCSystem 中重建
+    fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (PyObject,))> {
+        let graph = PortableGraph::capture(self.value_ref()?)
+            .map_err(|e| PicklingError::new_err(e.to_string()))?;
+        let restore = py
+            .import("xlang.xlang_py")?
+            .getattr("_restore_value")?
+            .unbind();
+        let state = PyBytes::new(py, &graph.to_bytes()).into_any().unbind();
+        Ok((restore, (state,)))
     }
 }
 
-impl Drop for VMFloat {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+impl VMValue {
+    fn wrap_result(
+        &self,
+        result: Result<XlangGCRef, VMVariableError>,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        match result {
+            Ok(mut value) => {
+                let py_object = xlang_gc_ref_to_py_object(&mut value, self.gc_system.clone(), py);
+                value.drop_ref();
+                py_object
+            }
+            Err(e) => Err(operators::vm_variable_error_to_pyerr(e)),
+        }
     }
 }
 
-#[pyclass(unsendable)]
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
-struct VMString {
-    gc_ref: XlangGCRef,
+struct VMInt {
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
-impl VMString {
-    fn create(gc: &mut GCSystem, value: String) -> Self {
+impl VMInt {
+    fn create(gc: &mut GCSystem, value: i64) -> PyResult<Self> {
         let gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMString::new(&value)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Ok(mut gc_system) => gc_system.new_object(XlangVMInt::new(value)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        VMString {
-            gc_ref,
+        Ok(VMInt {
+            gc_ref: AliasRef::new(gc_ref),
             gc_system: gc.gc_system.clone(),
-        }
+        })
     }
 }
 
-impl GCRef for VMString {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+        (__sub__, __rsub__, operators::checked_sub_as_vmobject),
+        (__mul__, __rmul__, operators::checked_mul_as_vmobject),
+        (__truediv__, __rtruediv__, try_div_as_vmobject),
+        (__and__, __rand__, try_and_as_vmobject),
+        (__or__, __ror__, try_or_as_vmobject),
+        (__xor__, __rxor__, try_xor_as_vmobject),
+        (__lshift__, __rlshift__, operators::checked_shift_left_as_vmobject),
+        (__rshift__, __rrshift__, operators::checked_shift_right_as_vmobject),
+    ],
+    unary_ops: [
+        (__neg__, operators::try_negate_as_vmobject),
+        (__invert__, try_not_as_vmobject),
+    ],
+    compare_ops: [
+        (__lt__, try_less_than_as_vmobject, false),
+        (__le__, try_greater_than_as_vmobject, true),
+        (__gt__, try_greater_than_as_vmobject, false),
+        (__ge__, try_less_than_as_vmobject, true),
+    ],
+    impl VMInt {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, value)")]
+        fn new(gc: &mut GCSystem, value: i64) -> PyResult<Self> {
+            VMInt::create(gc, value)
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn get_value(&self) -> PyResult<i64> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMInt>().value)
+        }
+
+        #[pyo3(text_signature = "($self, value)")]
+        fn set_value(&mut self, value: i64) -> PyResult<()> {
+            self.gc_ref.get_mut()?.as_type::<XlangVMInt>().value = value;
+            Ok(())
+        }
+
+        fn __repr__(&self) -> PyResult<String> {
+            Ok(format!("VMInt({})", self.get_value()?))
+        }
+
+        fn __str__(&self) -> PyResult<String> {
+            Ok(format!("{}", self.get_value()?))
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn clone(&mut self) -> PyResult<Self> {
+            let value = XlangVMInt::new(self.get_value()?);
+            let gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(value),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMInt {
+                gc_ref: AliasRef::new(gc_ref),
+                gc_system: self.gc_system.clone(),
+            })
+        }
+
+        #[pyo3(text_signature = "($self, py)")]
+        fn to_py(&self, py: Python) -> PyResult<PyObject> {
+            let value = self.get_value()?;
+            let py_int = PyInt::new(py, value);
+            Ok(py_int.into())
+        }
+
+        fn __mod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __rmod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __pow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, false, py)
+        }
+
+        fn __rpow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, true, py)
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
+
+        fn __int__(&self) -> PyResult<i64> {
+            operators::to_int(self.gc_ref.get()?)
+        }
+
+        fn __float__(&self) -> PyResult<f64> {
+            operators::to_float(self.gc_ref.get()?)
+        }
+
+        fn __index__(&self) -> PyResult<i64> {
+            self.get_value()
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __hash__(&self, py: Python) -> PyResult<isize> {
+            operators::hash_value(self.gc_ref.get()?, py)
+        }
     }
 }
 
-#[pymethods]
-impl VMString {
-    #[new]
-    #[pyo3(text_signature = "($cls, gc, value)")]
-    fn new(gc: &mut GCSystem, value: String) -> Self {
-        VMString::create(gc, value)
-    }
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_value(&self) -> String {
-        self.gc_ref.as_const_type::<XlangVMString>().value.clone()
-    }
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMFloat {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
 
-    #[pyo3(text_signature = "($self, value)")]
-    fn set_value(&mut self, value: String) {
-        self.gc_ref.as_type::<XlangVMString>().value = value;
+impl VMFloat {
+    fn create(gc: &mut GCSystem, value: f64) -> PyResult<Self> {
+        let gc_ref = match gc.gc_system.borrow_mut() {
+            Ok(mut gc_system) => gc_system.new_object(XlangVMFloat::new(value)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        Ok(VMFloat {
+            gc_ref: AliasRef::new(gc_ref),
+            gc_system: gc.gc_system.clone(),
+        })
     }
+}
 
-    fn __repr__(&self) -> PyResult<String> {
-        Ok(format!("VMString(\"{}\")", self.get_value()))
-    }
-    fn __str__(&self) -> PyResult<String> {
-        Ok(format!("{}", self.get_value()))
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+        (__sub__, __rsub__, operators::checked_sub_as_vmobject),
+        (__mul__, __rmul__, operators::checked_mul_as_vmobject),
+        (__truediv__, __rtruediv__, try_div_as_vmobject),
+    ],
+    unary_ops: [
+        (__neg__, operators::try_negate_as_vmobject),
+    ],
+    compare_ops: [
+        (__lt__, try_less_than_as_vmobject, false),
+        (__le__, try_greater_than_as_vmobject, true),
+        (__gt__, try_greater_than_as_vmobject, false),
+        (__ge__, try_less_than_as_vmobject, true),
+    ],
+    impl VMFloat {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, value)")]
+        fn new(gc: &mut GCSystem, value: f64) -> PyResult<Self> {
+            VMFloat::create(gc, value)
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn get_value(&self) -> PyResult<f64> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMFloat>().value)
+        }
+
+        #[pyo3(text_signature = "($self, value)")]
+        fn set_value(&mut self, value: f64) -> PyResult<()> {
+            self.gc_ref.get_mut()?.as_type::<XlangVMFloat>().value = value;
+            Ok(())
+        }
+
+        fn __repr__(&self) -> PyResult<String> {
+            Ok(format!("VMFloat({})", self.get_value()?))
+        }
+        fn __str__(&self) -> PyResult<String> {
+            Ok(format!("{}", self.get_value()?))
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn clone(&mut self) -> PyResult<Self> {
+            let value = XlangVMFloat::new(self.get_value()?);
+            let gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(value),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMFloat {
+                gc_ref: AliasRef::new(gc_ref),
+                gc_system: self.gc_system.clone(),
+            })
+        }
+
+        #[pyo3(text_signature = "($self, py)")]
+        fn to_py(&self, py: Python) -> PyResult<PyObject> {
+            let value = self.get_value()?;
+            let py_float = PyFloat::new(py, value);
+            Ok(py_float.into())
+        }
+
+        fn __mod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __rmod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __pow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, false, py)
+        }
+
+        fn __rpow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, true, py)
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
+
+        fn __int__(&self) -> PyResult<i64> {
+            operators::to_int(self.gc_ref.get()?)
+        }
+
+        fn __float__(&self) -> PyResult<f64> {
+            operators::to_float(self.gc_ref.get()?)
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __hash__(&self, py: Python) -> PyResult<isize> {
+            operators::hash_value(self.gc_ref.get()?, py)
+        }
     }
+}
 
-    fn __len__(&self) -> usize {
-        self.gc_ref.as_const_type::<XlangVMString>().value.len()
+
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMString {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
+
+impl VMString {
+    fn create(gc: &mut GCSystem, value: String) -> PyResult<Self> {
+        VMString::create_in(&gc.gc_system, &value)
     }
 
-    fn clone(&mut self) -> Self {
-        let value = XlangVMString::new(&self.get_value());
-        let gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(value),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        return VMString {
-            gc_ref,
-            gc_system: self.gc_system.clone(),
+    fn create_in(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, value: &str) -> PyResult<Self> {
+        let gc_ref = match gc_system.borrow_mut() {
+            Ok(mut gc_system) => gc_system.new_object(XlangVMString::new(value)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
+        Ok(VMString {
+            gc_ref: AliasRef::new(gc_ref),
+            gc_system: gc_system.clone(),
+        })
     }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&self, py: Python) -> PyResult<PyObject> {
-        let value = self.get_value();
-        let py_str = PyString::new(py, &value);
-        Ok(py_str.into())
+    fn chars(&self) -> PyResult<Vec<char>> {
+        Ok(self.gc_ref.get()?.as_const_type::<XlangVMString>().value.chars().collect())
     }
 }
 
-impl Drop for VMString {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+    ],
+    impl VMString {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, value)")]
+        fn new(gc: &mut GCSystem, value: String) -> PyResult<Self> {
+            VMString::create(gc, value)
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn get_value(&self) -> PyResult<String> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMString>().value.clone())
+        }
+
+        #[pyo3(text_signature = "($self, value)")]
+        fn set_value(&mut self, value: String) -> PyResult<()> {
+            self.gc_ref.get_mut()?.as_type::<XlangVMString>().value = value;
+            Ok(())
+        }
+
+        fn __repr__(&self) -> PyResult<String> {
+            Ok(format!("VMString(\"{}\")", self.get_value()?))
+        }
+        fn __str__(&self) -> PyResult<String> {
+            Ok(self.get_value()?.to_string())
+        }
+
+        // 按字符计数，与 Python 的 str 一致
+        fn __len__(&self) -> PyResult<usize> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMString>().value.chars().count())
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn byte_len(&self) -> PyResult<usize> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMString>().value.len())
+        }
+
+        fn __getitem__(&self, index: &Bound<'_, PyAny>) -> PyResult<VMString> {
+            let chars = self.chars()?;
+            if let Ok(slice) = index.downcast::<PySlice>() {
+                let indices = slice.indices(chars.len() as isize)?;
+                let mut value = String::with_capacity(indices.slicelength);
+                let mut i = indices.start;
+                for _ in 0..indices.slicelength {
+                    value.push(chars[i as usize]);
+                    i += indices.step;
+                }
+                return VMString::create_in(&self.gc_system, &value);
+            }
+            let mut i = index.extract::<isize>()?;
+            if i < 0 {
+                i += chars.len() as isize;
+            }
+            if i < 0 || i >= chars.len() as isize {
+                return Err(PyIndexError::new_err("string index out of range"));
+            }
+            VMString::create_in(&self.gc_system, &chars[i as usize].to_string())
+        }
+
+        fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+            operators::contains(self.gc_ref.get()?, &self.gc_system, item)
+        }
+
+        fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+            let items: Vec<VMString> = self
+                .chars()?
+                .iter()
+                .map(|c| VMString::create_in(&self.gc_system, &c.to_string()))
+                .collect::<PyResult<_>>()?;
+            Ok(PyList::new(py, items)?.try_iter()?.into_any().unbind())
+        }
+
+        #[pyo3(signature = (encoding = "utf-8", errors = "strict"))]
+        #[pyo3(text_signature = "($self, encoding='utf-8', errors='strict')")]
+        fn encode(&self, encoding: &str, errors: &str, py: Python) -> PyResult<VMBytes> {
+            let value = self.get_value()?;
+            let encoded = PyString::new(py, &value).call_method1("encode", (encoding, errors))?;
+            VMBytes::create_in(&self.gc_system, encoded.extract::<Vec<u8>>()?)
+        }
+
+        // 解码字节；传入 Python bytes 且未提供 gc 时使用默认堆
+        #[staticmethod]
+        #[pyo3(signature = (b, encoding = "utf-8", errors = "strict", gc = None))]
+        #[pyo3(text_signature = "(b, encoding='utf-8', errors='strict', gc=None)")]
+        fn from_bytes(
+            b: &Bound<'_, PyAny>,
+            encoding: &str,
+            errors: &str,
+            gc: Option<PyRef<GCSystem>>,
+            py: Python,
+        ) -> PyResult<VMString> {
+            let (data, gc_system) = if let Ok(vm_bytes) = b.extract::<PyRef<VMBytes>>() {
+                (vm_bytes.get_value()?, vm_bytes.gc_system.clone())
+            } else {
+                let gc_system = match gc {
+                    Some(gc) => gc.gc_system.clone(),
+                    None => default_gc_system(),
+                };
+                (b.extract::<Vec<u8>>()?, gc_system)
+            };
+            let decoded = PyBytes::new(py, &data).call_method1("decode", (encoding, errors))?;
+            VMString::create_in(&gc_system, &decoded.extract::<String>()?)
+        }
+
+        fn clone(&mut self) -> PyResult<Self> {
+            let value = XlangVMString::new(&self.get_value()?);
+            let gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(value),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMString {
+                gc_ref: AliasRef::new(gc_ref),
+                gc_system: self.gc_system.clone(),
+            })
+        }
+
+        #[pyo3(text_signature = "($self, py)")]
+        fn to_py(&self, py: Python) -> PyResult<PyObject> {
+            let value = self.get_value()?;
+            let py_str = PyString::new(py, &value);
+            Ok(py_str.into())
+        }
+
+        fn __mod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __rmod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
+
+        fn __int__(&self) -> PyResult<i64> {
+            operators::to_int(self.gc_ref.get()?)
+        }
+
+        fn __float__(&self) -> PyResult<f64> {
+            operators::to_float(self.gc_ref.get()?)
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __hash__(&self, py: Python) -> PyResult<isize> {
+            operators::hash_value(self.gc_ref.get()?, py)
+        }
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMNull {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
 impl VMNull {
-    fn create(gc: &mut GCSystem) -> Self {
+    fn create(gc: &mut GCSystem) -> PyResult<Self> {
         let gc_ref = match gc.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XlangVMNull::new()),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        VMNull {
-            gc_ref,
+        Ok(VMNull {
+            gc_ref: AliasRef::new(gc_ref),
             gc_system: gc.gc_system.clone(),
-        }
-    }
-}
-
-impl GCRef for VMNull {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+        })
     }
 }
 
@@ -344,14 +847,14 @@ impl GCRef for VMNull {
 impl VMNull {
     #[new]
     #[pyo3(text_signature = "($cls, gc)")]
-    fn new(gc: &mut GCSystem) -> Self {
+    fn new(gc: &mut GCSystem) -> PyResult<Self> {
         VMNull::create(gc)
     }
 
     #[pyo3(text_signature = "($self)")]
     fn get_value(&self) -> PyResult<PyObject> {
         let py_none = Python::with_gil(|py| py.None());
-        Ok(py_none.into())
+        Ok(py_none)
     }
     fn __repr__(&self) -> PyResult<String> {
         Ok("VMNull()".to_string())
@@ -361,161 +864,512 @@ impl VMNull {
     }
 
     #[pyo3(text_signature = "($self)")]
-    fn clone(&mut self) -> Self {
+    fn clone(&mut self) -> PyResult<Self> {
         let gc_ref = match self.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XlangVMNull::new()),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        return VMNull {
-            gc_ref,
+        Ok(VMNull {
+            gc_ref: AliasRef::new(gc_ref),
             gc_system: self.gc_system.clone(),
-        };
+        })
     }
 
     #[pyo3(text_signature = "($self, py)")]
     fn to_py(&self, py: Python) -> PyResult<PyObject> {
         let py_none = py.None();
-        Ok(py_none.into())
+        Ok(py_none)
     }
-}
 
-impl Drop for VMNull {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+    fn __bool__(&self) -> bool {
+        false
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<isize> {
+        operators::hash_value(self.gc_ref.get()?, py)
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMBytes {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
 impl VMBytes {
-    fn create(gc: &mut GCSystem, value: Vec<u8>) -> Self {
-        let gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMBytes::new(&value)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+    fn create(gc: &mut GCSystem, value: Vec<u8>) -> PyResult<Self> {
+        VMBytes::create_in(&gc.gc_system, value)
+    }
+
+    fn create_in(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>, value: Vec<u8>) -> PyResult<Self> {
+        let gc_ref = match gc_system.borrow_mut() {
+            Ok(mut gc_system) => gc_system.new_object(vm_bytes_from_vec(value)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        VMBytes {
-            gc_ref,
-            gc_system: gc.gc_system.clone(),
+        Ok(VMBytes {
+            gc_ref: AliasRef::new(gc_ref),
+            gc_system: gc_system.clone(),
+        })
+    }
+
+    // 与 bytearray 一致，存在导出的视图时不能改变长度或替换存储
+    fn ensure_resizable(&self) -> PyResult<()> {
+        if buffer::is_exported(self.gc_ref.get()?) {
+            return Err(PyBufferError::new_err(
+                "Existing exports of data: object cannot be re-sized",
+            ));
         }
+        Ok(())
     }
 }
 
-impl GCRef for VMBytes {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
+// 接受 buffer 协议对象或整数可迭代对象，与 bytearray 的构造规则一致
+fn bytes_from_object(obj: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    match copy_from_buffer(obj) {
+        Ok(data) => Ok(data),
+        Err(_) if !obj.is_instance_of::<PyString>() => obj.extract::<Vec<u8>>(),
+        Err(e) => Err(e),
     }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
+}
+
+// 将 Python 索引规范化到 [0, len)
+fn normalize_index(index: isize, len: usize) -> PyResult<usize> {
+    let index = if index < 0 { index + len as isize } else { index };
+    if index < 0 || index >= len as isize {
+        return Err(PyIndexError::new_err("bytes index out of range"));
     }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
+    Ok(index as usize)
+}
+
+// 在 haystack[start..end] 中查找 needle，返回绝对位置
+fn find_subslice(haystack: &[u8], needle: &[u8], start: usize, end: usize) -> Option<usize> {
+    if start > end || end > haystack.len() {
+        return None;
     }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+    if needle.is_empty() {
+        return Some(start);
     }
+    haystack[start..end]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + start)
 }
 
-#[pymethods]
-impl VMBytes {
-    #[new]
-    #[pyo3(text_signature = "($cls, gc, value)")]
-    fn new(gc: &mut GCSystem, value: Vec<u8>) -> Self {
-        VMBytes::create(gc, value)
-    }
+// XlangVMBytes::new 会复制传入的切片，这里直接接管已经复制好的 Vec
+fn vm_bytes_from_vec(value: Vec<u8>) -> XlangVMBytes {
+    let mut bytes = XlangVMBytes::new(&Vec::new());
+    bytes.value = value;
+    bytes
+}
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_value(&self) -> Vec<u8> {
-        self.gc_ref.as_const_type::<XlangVMBytes>().value.clone()
+// 从任意支持 buffer 协议的对象中复制数据，只复制一次
+fn copy_from_buffer(obj: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    if let Ok(bytes) = obj.downcast::<PyBytes>() {
+        return Ok(bytes.as_bytes().to_vec());
     }
-
-    #[pyo3(text_signature = "($self, value)")]
-    fn set_value(&mut self, value: Vec<u8>) {
-        self.gc_ref.as_type::<XlangVMBytes>().value = value;
+    unsafe {
+        let mut view = MaybeUninit::<ffi::Py_buffer>::uninit();
+        if ffi::PyObject_GetBuffer(obj.as_ptr(), view.as_mut_ptr(), ffi::PyBUF_SIMPLE) == -1 {
+            return Err(PyErr::fetch(obj.py()));
+        }
+        let mut view = view.assume_init();
+        let data = if view.len == 0 {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(view.buf as *const u8, view.len as usize).to_vec()
+        };
+        ffi::PyBuffer_Release(&mut view);
+        Ok(data)
     }
+}
 
-    fn __repr__(&self) -> PyResult<String> {
-        // Represent bytes as a string, similar to Python's b"..."
-        // This might need a more robust way to escape non-printable characters
-        let bytes_val = self.get_value();
-        let repr_str = bytes_val
-            .iter()
-            .map(|b| {
-                if *b >= 32 && *b <= 126 {
-                    (*b as char).to_string()
-                } else {
-                    format!("\\x{:02x}", b)
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+    ],
+    impl VMBytes {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, value)")]
+        fn new(gc: &mut GCSystem, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+            VMBytes::create(gc, copy_from_buffer(value)?)
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn get_value(&self) -> PyResult<Vec<u8>> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value.clone())
+        }
+
+        #[pyo3(text_signature = "($self, value)")]
+        fn set_value(slf: &Bound<'_, Self>, value: &Bound<'_, PyAny>) -> PyResult<()> {
+            let value = copy_from_buffer(value)?;
+            let mut this = slf.try_borrow_mut()?;
+            this.ensure_resizable()?;
+            this.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value = value;
+            Ok(())
+        }
+
+        // 视图直接指向虚拟机中的存储，导出期间不允许改变长度
+        unsafe fn __getbuffer__(
+            slf: PyRef<'_, Self>,
+            view: *mut ffi::Py_buffer,
+            flags: c_int,
+        ) -> PyResult<()> {
+            let (id, data, len) = buffer::export(slf.gc_ref.get()?);
+            let ret = ffi::PyBuffer_FillInfo(
+                view,
+                slf.as_ptr(),
+                data as *mut c_void,
+                len as ffi::Py_ssize_t,
+                0,
+                flags,
+            );
+            if ret == -1 {
+                buffer::release(id);
+                return Err(PyErr::fetch(slf.py()));
+            }
+            (*view).internal = id as *mut c_void;
+            Ok(())
+        }
+
+        unsafe fn __releasebuffer__(&self, view: *mut ffi::Py_buffer) {
+            buffer::release((*view).internal as usize);
+        }
+
+        fn __repr__(&self) -> PyResult<String> {
+            // Represent bytes as a string, similar to Python's b"..."
+            // This might need a more robust way to escape non-printable characters
+            let bytes_val = self.get_value()?;
+            let repr_str = bytes_val
+                .iter()
+                .map(|b| {
+                    if *b >= 32 && *b <= 126 {
+                        (*b as char).to_string()
+                    } else {
+                        format!("\\x{:02x}", b)
+                    }
+                })
+                .collect::<String>();
+            Ok(format!("VMBytes(b\"{}\")", repr_str))
+        }
+
+        fn __str__(&self) -> PyResult<String> {
+            // Convert bytes to a string representation
+            let bytes_val = self.get_value()?;
+            let str_val = String::from_utf8_lossy(&bytes_val);
+            Ok(str_val.to_string())
+        }
+
+        fn __len__(&self) -> PyResult<usize> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value.len())
+        }
+
+        fn __getitem__(&self, index: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            let data = &self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value;
+            if let Ok(slice) = index.downcast::<PySlice>() {
+                let indices = slice.indices(data.len() as isize)?;
+                let mut value = Vec::with_capacity(indices.slicelength);
+                let mut i = indices.start;
+                for _ in 0..indices.slicelength {
+                    value.push(data[i as usize]);
+                    i += indices.step;
+                }
+                let vm_bytes = VMBytes::create_in(&self.gc_system, value)?;
+                return Ok(Py::new(py, vm_bytes)?.into_any());
+            }
+            let i = normalize_index(index.extract::<isize>()?, data.len())?;
+            Ok(data[i].into_pyobject(py)?.into_any().unbind())
+        }
+
+        // 原地修改，脚本持有的引用能看到变化
+        fn __setitem__(
+            slf: &Bound<'_, Self>,
+            index: &Bound<'_, PyAny>,
+            value: &Bound<'_, PyAny>,
+        ) -> PyResult<()> {
+            // value 可能就是 slf 本身，必须在可变借用之前复制数据
+            let new_values = match index.downcast::<PySlice>() {
+                Ok(_) => Some(bytes_from_object(value)?),
+                Err(_) => None,
+            };
+            let mut this = slf.try_borrow_mut()?;
+            let len = this.gc_ref.get()?.as_const_type::<XlangVMBytes>().value.len();
+            if let (Ok(slice), Some(new_values)) = (index.downcast::<PySlice>(), new_values) {
+                let indices = slice.indices(len as isize)?;
+                if indices.step == 1 {
+                    if new_values.len() != indices.slicelength {
+                        this.ensure_resizable()?;
+                    }
+                    let start = indices.start as usize;
+                    let stop = start + indices.slicelength;
+                    let data = &mut this.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value;
+                    data.splice(start..stop, new_values);
+                    return Ok(());
+                }
+                if new_values.len() != indices.slicelength {
+                    return Err(PyValueError::new_err(format!(
+                        "attempt to assign bytes of size {} to extended slice of size {}",
+                        new_values.len(),
+                        indices.slicelength
+                    )));
                 }
+                let data = &mut this.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value;
+                let mut i = indices.start;
+                for byte in new_values {
+                    data[i as usize] = byte;
+                    i += indices.step;
+                }
+                return Ok(());
+            }
+            let i = normalize_index(index.extract::<isize>()?, len)?;
+            let byte = value
+                .extract::<u8>()
+                .map_err(|_| PyValueError::new_err("byte must be in range(0, 256)"))?;
+            this.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value[i] = byte;
+            Ok(())
+        }
+
+        fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+            let data = &self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value;
+            Ok(PyBytes::new(py, data).try_iter()?.into_any().unbind())
+        }
+
+        #[pyo3(text_signature = "($self, values)")]
+        fn extend(slf: &Bound<'_, Self>, values: &Bound<'_, PyAny>) -> PyResult<()> {
+            let values = bytes_from_object(values)?;
+            let mut this = slf.try_borrow_mut()?;
+            this.ensure_resizable()?;
+            this.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value.extend(values);
+            Ok(())
+        }
+
+        #[pyo3(text_signature = "($self, byte)")]
+        fn append(&mut self, byte: &Bound<'_, PyAny>) -> PyResult<()> {
+            let byte = byte
+                .extract::<u8>()
+                .map_err(|_| PyValueError::new_err("byte must be in range(0, 256)"))?;
+            self.ensure_resizable()?;
+            self.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value.push(byte);
+            Ok(())
+        }
+
+        #[pyo3(text_signature = "($self, size)")]
+        fn truncate(&mut self, size: usize) -> PyResult<()> {
+            self.ensure_resizable()?;
+            self.gc_ref.get_mut()?.as_type::<XlangVMBytes>().value.truncate(size);
+            Ok(())
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn hex(&self) -> PyResult<String> {
+            let data = &self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value;
+            Ok(data.iter().map(|byte| format!("{:02x}", byte)).collect())
+        }
+
+        // 未提供 gc 时使用默认堆
+        #[staticmethod]
+        #[pyo3(signature = (string, gc = None))]
+        #[pyo3(text_signature = "(string, gc=None)")]
+        fn fromhex(string: &str, gc: Option<PyRef<GCSystem>>) -> PyResult<VMBytes> {
+            let digits: Vec<char> = string.chars().filter(|c| !c.is_whitespace()).collect();
+            if !digits.len().is_multiple_of(2) {
+                return Err(PyValueError::new_err(
+                    "fromhex() arg must contain an even number of hexadecimal digits",
+                ));
+            }
+            let mut value = Vec::with_capacity(digits.len() / 2);
+            for pair in digits.chunks(2) {
+                let text: String = pair.iter().collect();
+                let byte = u8::from_str_radix(&text, 16).map_err(|_| {
+                    PyValueError::new_err(format!(
+                        "non-hexadecimal number found in fromhex() arg: {:?}",
+                        text
+                    ))
+                })?;
+                value.push(byte);
+            }
+            let gc_system = match gc {
+                Some(gc) => gc.gc_system.clone(),
+                None => default_gc_system(),
+            };
+            VMBytes::create_in(&gc_system, value)
+        }
+
+        #[pyo3(signature = (sub, start = None, end = None))]
+        #[pyo3(text_signature = "($self, sub, start=None, end=None)")]
+        fn find(
+            &self,
+            sub: &Bound<'_, PyAny>,
+            start: Option<isize>,
+            end: Option<isize>,
+        ) -> PyResult<isize> {
+            let data = &self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value;
+            let needle = match sub.extract::<u8>() {
+                Ok(byte) => vec![byte],
+                Err(_) => copy_from_buffer(sub)?,
+            };
+            // 与 Python 的切片边界规则一致
+            let clamp = |index: isize| -> usize {
+                let index = if index < 0 { index + data.len() as isize } else { index };
+                index.clamp(0, data.len() as isize) as usize
+            };
+            let start = clamp(start.unwrap_or(0));
+            let end = clamp(end.unwrap_or(data.len() as isize));
+            Ok(find_subslice(data, &needle, start, end).map_or(-1, |pos| pos as isize))
+        }
+
+        #[pyo3(text_signature = "($self, prefix)")]
+        fn startswith(&self, prefix: &Bound<'_, PyAny>) -> PyResult<bool> {
+            let prefix = copy_from_buffer(prefix)?;
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMBytes>().value.starts_with(&prefix))
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn clone(&mut self) -> PyResult<Self> {
+            let value = vm_bytes_from_vec(self.get_value()?);
+            let gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(value),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMBytes {
+                gc_ref: AliasRef::new(gc_ref),
+                gc_system: self.gc_system.clone(),
             })
-            .collect::<String>();
-        Ok(format!("VMBytes(b\"{}\")", repr_str))
-    }
+        }
 
-    fn __str__(&self) -> PyResult<String> {
-        // Convert bytes to a string representation
-        let bytes_val = self.get_value();
-        let str_val = String::from_utf8_lossy(&bytes_val);
-        Ok(str_val.to_string())
+        #[pyo3(text_signature = "($self, py)")]
+        fn to_py(&self, py: Python) -> PyResult<PyObject> {
+            let value = self.get_value()?;
+            let py_bytes = PyBytes::new(py, &value);
+            Ok(py_bytes.into())
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
+
+        fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+            operators::contains(self.gc_ref.get()?, &self.gc_system, item)
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        // 与 bytearray 一样，可变的字节串不可哈希
     }
+}
 
-    fn __len__(&self) -> usize {
-        self.gc_ref.as_const_type::<XlangVMBytes>().value.len()
+// 整数值且在 i64 范围内的浮点数返回对应的整数
+fn integral_float(value: f64) -> Option<i64> {
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if value.fract() == 0.0 && (-LIMIT..LIMIT).contains(&value) {
+        Some(value as i64)
+    } else {
+        None
     }
+}
 
-    #[pyo3(text_signature = "($self)")]
-    fn clone(&mut self) -> Self {
-        let value = XlangVMBytes::new(&self.get_value());
-        let gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(value),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        return VMBytes {
-            gc_ref,
-            gc_system: self.gc_system.clone(),
-        };
+// Helper function to handle Python basic types conversion using GC system
+// 读取 Python range 的边界；虚拟机的 VMRange 没有步长，只接受步长为 1 的 range
+fn python_range_bounds(obj: &Bound<'_, PyAny>) -> PyResult<Option<(i64, i64)>> {
+    if unsafe { ffi::PyRange_Check(obj.as_ptr()) } == 0 {
+        return Ok(None);
+    }
+    let step = obj.getattr("step")?.extract::<i64>()?;
+    if step != 1 {
+        return Err(PyValueError::new_err(format!(
+            "Cannot convert range with step {} to VMRange, only step 1 is supported",
+            step
+        )));
+    }
+    let start = obj.getattr("start")?.extract::<i64>()?;
+    let stop = obj.getattr("stop")?.extract::<i64>()?;
+    Ok(Some((start, stop)))
+}
+
+// 元组持有元素后释放这里的原生引用
+fn new_tuple_from_refs(mut items: Vec<XlangGCRef>, gc_system: &mut XlangGCSystem) -> XlangGCRef {
+    let mut item_refs: Vec<&mut XlangGCRef> = items.iter_mut().collect();
+    let tuple = gc_system.new_object(XlangVMTuple::new(&mut item_refs));
+    for mut item in items {
+        item.drop_ref();
     }
+    tuple
+}
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&self, py: Python) -> PyResult<PyObject> {
-        let value = self.get_value();
-        let py_bytes = PyBytes::new(py, &value);
-        Ok(py_bytes.into())
+// 构建失败时释放已经创建的元素，返回原来的错误
+fn release_all(items: Vec<XlangGCRef>, e: PyErr) -> PyErr {
+    for mut item in items {
+        item.drop_ref();
     }
+    e
 }
 
-impl Drop for VMBytes {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+// 逐个转换元素，任何一步失败都释放已经转换的元素
+fn new_tuple_from_items<'py>(
+    items: impl Iterator<Item = Bound<'py, PyAny>>,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    what: &str,
+) -> PyResult<XlangGCRef> {
+    let mut xlang_list: Vec<XlangGCRef> = Vec::new();
+    let mut result = Ok(());
+    for item in items {
+        match extract_xlang_gc_ref_with_gc_arc(&item, gc_system.clone()) {
+            Ok(item_ref) => xlang_list.push(item_ref),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
     }
+    let new_gc_ref = result.and_then(|()| match gc_system.borrow_mut() {
+        Ok(mut mut_gc_system_guard) => Ok(mut_gc_system_guard
+            .new_object(XlangVMTuple::new(&mut xlang_list.iter_mut().collect()))),
+        Err(e) => Err(borrow_error(&format!("Failed to borrow GC system for {}", what), e)),
+    });
+    for item in &mut xlang_list {
+        item.drop_ref();
+    }
+    new_gc_ref
 }
-// Helper function to handle Python basic types conversion using GC system
+
 fn extract_xlang_gc_ref_with_gc_arc(
     obj: &Bound<'_, PyAny>,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 ) -> PyResult<XlangGCRef> {
     // First try to extract as a VM type
-    if let Ok(gc_ref) = extract_xlang_gc_ref(obj) {
+    if let Some(gc_ref) = extract_own_gc_ref(obj, heap_core(&gc_system))? {
         return Ok(gc_ref);
     }
     // If not a VM type, handle basic Python types
-    if let Ok(py_int) = obj.downcast::<PyInt>() {
+    // bool 是 int 的子类，需要先于 int 判断
+    if let Ok(py_bool) = obj.downcast::<PyBool>() {
+        let xlang_bool = XlangVMBoolean::new(py_bool.is_true());
+        let new_gc_ref = match gc_system.borrow_mut() {
+            Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_bool),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyBool conversion", e)),
+        };
+        Ok(new_gc_ref)
+    } else if let Ok(py_int) = obj.downcast::<PyInt>() {
         let value = py_int.extract::<i64>()?;
         let xlang_int = XlangVMInt::new(value);
         let new_gc_ref = match gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_int),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyInt conversion");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyInt conversion", e)),
         };
         Ok(new_gc_ref)
     } else if let Ok(py_float) = obj.downcast::<PyFloat>() {
@@ -523,9 +1377,7 @@ fn extract_xlang_gc_ref_with_gc_arc(
         let xlang_float = XlangVMFloat::new(value);
         let new_gc_ref = match gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_float),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyFloat conversion");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyFloat conversion", e)),
         };
         Ok(new_gc_ref)
     } else if let Ok(py_str) = obj.downcast::<PyString>() {
@@ -533,9 +1385,7 @@ fn extract_xlang_gc_ref_with_gc_arc(
         let xlang_string = XlangVMString::new(&value);
         let new_gc_ref = match gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_string),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyString conversion");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyString conversion", e)),
         };
         Ok(new_gc_ref)
     } else if let Ok(py_bytes) = obj.downcast::<PyBytes>() {
@@ -543,54 +1393,25 @@ fn extract_xlang_gc_ref_with_gc_arc(
         let xlang_bytes = XlangVMBytes::new(&value);
         let new_gc_ref = match gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_bytes),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyBytes conversion");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyBytes conversion", e)),
         };
         Ok(new_gc_ref)
     } else if let Ok(py_list) = obj.downcast::<PyList>() {
-        let mut xlang_list: Vec<XlangGCRef> = Vec::new();
-        for item in py_list.iter() {
-            let item_ref = extract_xlang_gc_ref_with_gc_arc(&item, gc_system.clone())?;
-            xlang_list.push(item_ref);
-        }
-        let new_gc_ref = match gc_system.borrow_mut() {
-            Ok(mut mut_gc_system_guard) => mut_gc_system_guard
-                .new_object(XlangVMTuple::new(&mut xlang_list.iter_mut().collect())),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyList conversion");
-            }
-        };
-        for item in &mut xlang_list {
-            item.drop_ref();
-        }
-        Ok(new_gc_ref)
+        new_tuple_from_items(py_list.iter(), &gc_system, "PyList conversion")
     } else if let Ok(py_tuple) = obj.downcast::<PyTuple>() {
-        // Changed py_list to py_tuple for clarity
-        let mut xlang_list: Vec<XlangGCRef> = Vec::new();
-        for item in py_tuple.iter() {
-            // Changed py_list to py_tuple
-            let item_ref = extract_xlang_gc_ref_with_gc_arc(&item, gc_system.clone())?; // Changed Arc::clone to gc_system.clone()
-            xlang_list.push(item_ref);
-        }
+        new_tuple_from_items(py_tuple.iter(), &gc_system, "PyTuple conversion")
+    } else if obj.downcast::<PyNone>().is_ok() {
+        let xlang_none = XlangVMNull::new();
         let new_gc_ref = match gc_system.borrow_mut() {
-            Ok(mut mut_gc_system_guard) => mut_gc_system_guard
-                .new_object(XlangVMTuple::new(&mut xlang_list.iter_mut().collect())),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyTuple conversion");
-            }
+            Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_none),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for PyNone conversion", e)),
         };
-        for item in &mut xlang_list {
-            item.drop_ref();
-        }
         Ok(new_gc_ref)
-    } else if let Ok(_) = obj.downcast::<PyNone>() {
-        let xlang_none = XlangVMNull::new();
+    } else if let Some((start, end)) = python_range_bounds(obj)? {
+        let xlang_range = XlangVMRange::new(start, end);
         let new_gc_ref = match gc_system.borrow_mut() {
-            Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_none),
-            Err(_) => {
-                panic!("Failed to borrow GC system for PyNone conversion");
-            }
+            Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_range),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system for range conversion", e)),
         };
         Ok(new_gc_ref)
     } else {
@@ -606,11 +1427,15 @@ fn extract_xlang_gc_ref_with_gc(
     gc_system: &mut XlangGCSystem,
 ) -> PyResult<XlangGCRef> {
     // First try to extract as a VM type
-    if let Ok(gc_ref) = extract_xlang_gc_ref(obj) {
+    if let Some(gc_ref) = extract_own_gc_ref(obj, gc_system)? {
         return Ok(gc_ref);
     }
     // If not a VM type, handle basic Python types
-    if let Ok(py_int) = obj.downcast::<PyInt>() {
+    // bool 是 int 的子类，需要先于 int 判断
+    if let Ok(py_bool) = obj.downcast::<PyBool>() {
+        let new_gc_ref = gc_system.new_object(XlangVMBoolean::new(py_bool.is_true()));
+        Ok(new_gc_ref)
+    } else if let Ok(py_int) = obj.downcast::<PyInt>() {
         let value = py_int.extract::<i64>()?;
         let xlang_int = XlangVMInt::new(value);
         let new_gc_ref = gc_system.new_object(xlang_int);
@@ -633,8 +1458,15 @@ fn extract_xlang_gc_ref_with_gc(
     } else if let Ok(py_list) = obj.downcast::<PyList>() {
         let mut xlang_list: Vec<XlangGCRef> = Vec::new();
         for item in py_list.iter() {
-            let item_ref = extract_xlang_gc_ref_with_gc(&item, gc_system)?;
-            xlang_list.push(item_ref);
+            match extract_xlang_gc_ref_with_gc(&item, gc_system) {
+                Ok(item_ref) => xlang_list.push(item_ref),
+                Err(e) => {
+                    for item in &mut xlang_list {
+                        item.drop_ref();
+                    }
+                    return Err(e);
+                }
+            }
         }
         let new_gc_ref =
             gc_system.new_object(XlangVMTuple::new(&mut xlang_list.iter_mut().collect()));
@@ -645,66 +1477,161 @@ fn extract_xlang_gc_ref_with_gc(
     } else if let Ok(py_list) = obj.downcast::<PyTuple>() {
         let mut xlang_list: Vec<XlangGCRef> = Vec::new();
         for item in py_list.iter() {
-            let item_ref = extract_xlang_gc_ref_with_gc(&item, gc_system)?;
-            xlang_list.push(item_ref);
+            match extract_xlang_gc_ref_with_gc(&item, gc_system) {
+                Ok(item_ref) => xlang_list.push(item_ref),
+                Err(e) => {
+                    for item in &mut xlang_list {
+                        item.drop_ref();
+                    }
+                    return Err(e);
+                }
+            }
         }
         let new_gc_ref =
             gc_system.new_object(XlangVMTuple::new(&mut xlang_list.iter_mut().collect()));
         for item in &mut xlang_list {
             item.drop_ref();
         }
-        Ok(new_gc_ref)
-    } else if let Ok(_) = obj.downcast::<PyNone>() {
-        let xlang_none = XlangVMNull::new();
-        let new_gc_ref = gc_system.new_object(xlang_none);
-        Ok(new_gc_ref)
+        Ok(new_gc_ref)
+    } else if obj.downcast::<PyNone>().is_ok() {
+        let xlang_none = XlangVMNull::new();
+        let new_gc_ref = gc_system.new_object(xlang_none);
+        Ok(new_gc_ref)
+    } else if let Some((start, end)) = python_range_bounds(obj)? {
+        let new_gc_ref = gc_system.new_object(XlangVMRange::new(start, end));
+        Ok(new_gc_ref)
+    } else {
+        Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "Expected a xlang VM type or basic Python type for extraction",
+        ))
+    }
+}
+
+// Helper function to extract XlangGCRef from a PyObject holding one of our VM types
+// This function will need to be updated as more types are added or a more generic solution is found.
+fn extract_xlang_gc_ref(obj: &Bound<'_, PyAny>) -> PyResult<XlangGCRef> {
+    match obj.downcast::<VMValue>() {
+        Ok(value) => Ok(value.borrow().value_ref()?.clone().clone_ref()),
+        Err(_) => Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "Expected a xlang VM value (VMValue subclass)",
+        )),
+    }
+}
+
+fn heap_core(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> *const XlangGCSystem {
+    gc_system.data_ptr()
+}
+
+// 混用其他堆的值会同时破坏两个堆的引用计数
+fn check_core(what: &str, value_heap: *const XlangGCSystem, heap: *const XlangGCSystem) -> PyResult<()> {
+    if value_heap == heap {
+        Ok(())
+    } else {
+        Err(CrossHeapError::new_err(format!(
+            "{} belongs to a different GCSystem; use gc.adopt() to copy it into this heap",
+            what
+        )))
+    }
+}
+
+pub(crate) fn check_same_heap(
+    what: &str,
+    value_heap: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+) -> PyResult<()> {
+    check_core(what, heap_core(value_heap), heap_core(gc_system))
+}
+
+// 取出 VM 值的新引用，值必须属于 heap；obj 不是 VM 值时返回 None
+fn extract_own_gc_ref(
+    obj: &Bound<'_, PyAny>,
+    heap: *const XlangGCSystem,
+) -> PyResult<Option<XlangGCRef>> {
+    let value = match obj.downcast::<VMValue>() {
+        Ok(value) => value.borrow(),
+        Err(_) => return Ok(None),
+    };
+    let type_name = obj.get_type().name()?;
+    check_core(&type_name.to_string(), heap_core(&value.gc_system), heap)?;
+    Ok(Some(value.value_ref()?.clone().clone_ref()))
+}
+
+/// Like `extract_xlang_gc_ref`, but raises `CrossHeapError` for values of
+/// another heap.
+pub(crate) fn extract_xlang_gc_ref_in(
+    obj: &Bound<'_, PyAny>,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+) -> PyResult<XlangGCRef> {
+    extract_own_gc_ref(obj, heap_core(gc_system))?.ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "Expected a xlang VM value (VMValue subclass)",
+        )
+    })
+}
+
+// 判断 Python 对象是否包装了同一个堆对象
+fn is_same_object(gc_ref: &XlangGCRef, other: &Bound<'_, PyAny>) -> bool {
+    match extract_xlang_gc_ref(other) {
+        Ok(mut other_ref) => {
+            let same = other_ref == *gc_ref;
+            other_ref.drop_ref();
+            same
+        }
+        Err(_) => false,
+    }
+}
+
+// 堆对象的地址，在对象存活期间唯一
+fn object_id(gc_ref: &XlangGCRef) -> usize {
+    gc_ref.get_const_reference() as *const () as usize
+}
+
+// 与虚拟机 typeof 指令返回的类型名一致
+fn vm_type_name(gc_ref: &XlangGCRef) -> &'static str {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        "int"
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        "float"
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        "string"
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        "bool"
+    } else if gc_ref.isinstance::<XlangVMTuple>() {
+        "tuple"
+    } else if gc_ref.isinstance::<XlangVMLambda>() {
+        "lambda"
+    } else if gc_ref.isinstance::<XlangVMNull>() {
+        "null"
+    } else if gc_ref.isinstance::<XlangVMKeyVal>() {
+        "keyval"
+    } else if gc_ref.isinstance::<XlangVMNamed>() {
+        "named"
+    } else if gc_ref.isinstance::<XlangVMRange>() {
+        "range"
+    } else if gc_ref.isinstance::<XlangVMWrapper>() {
+        "wrapper"
+    } else if gc_ref.isinstance::<XlangVMInstructions>() {
+        "instructions"
+    } else if gc_ref.isinstance::<XlangVMBytes>() {
+        "bytes"
+    } else if gc_ref.isinstance::<XlangVMSet>() {
+        "set"
     } else {
-        Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-            "Expected a xlang VM type or basic Python type for extraction",
-        ))
+        ""
     }
 }
 
-// Helper function to extract XlangGCRef from a PyObject holding one of our VM types
-// This function will need to be updated as more types are added or a more generic solution is found.
-fn extract_xlang_gc_ref(obj: &Bound<'_, PyAny>) -> PyResult<XlangGCRef> {
-    if let Ok(mut vm_int) = obj.extract::<PyRefMut<VMInt>>() {
-        Ok(vm_int.gc_ref.clone_ref())
-    } else if let Ok(mut vm_float) = obj.extract::<PyRefMut<VMFloat>>() {
-        Ok(vm_float.gc_ref.clone_ref())
-    } else if let Ok(mut vm_string) = obj.extract::<PyRefMut<VMString>>() {
-        Ok(vm_string.gc_ref.clone_ref())
-    } else if let Ok(mut vm_null) = obj.extract::<PyRefMut<VMNull>>() {
-        Ok(vm_null.gc_ref.clone_ref())
-    } else if let Ok(mut vm_bytes) = obj.extract::<PyRefMut<VMBytes>>() {
-        Ok(vm_bytes.gc_ref.clone_ref())
-    } else if let Ok(mut vm_key_val) = obj.extract::<PyRefMut<VMKeyVal>>() {
-        Ok(vm_key_val.gc_ref.clone_ref())
-    } else if let Ok(mut vm_named) = obj.extract::<PyRefMut<VMNamed>>() {
-        Ok(vm_named.gc_ref.clone_ref())
-    } else if let Ok(mut vm_tuple) = obj.extract::<PyRefMut<VMTuple>>() {
-        Ok(vm_tuple.gc_ref.clone_ref())
-    } else if let Ok(mut vm_wrapper) = obj.extract::<PyRefMut<VMWrapper>>() {
-        Ok(vm_wrapper.gc_ref.clone_ref())
-    } else if let Ok(mut vm_range) = obj.extract::<PyRefMut<VMRange>>() {
-        Ok(vm_range.gc_ref.clone_ref())
-    } else if let Ok(mut vm_wrapped_pyfunction) = obj.extract::<PyRefMut<WrappedPyFunction>>() {
-        match &mut vm_wrapped_pyfunction.function_object {
-            Some(wrapped) => {
-                let xlang_ref = wrapped.clone_ref();
-                Ok(xlang_ref)
-            }
-            None => Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-                "WrappedPyFunction is None",
-            )),
+fn vm_repr(gc_ref: &XlangGCRef) -> PyResult<String> {
+    match try_repr_vmobject(&mut gc_ref.clone(), None) {
+        Ok(repr) => Ok(repr),
+        Err(mut e) => {
+            let message = e.to_string();
+            e.consume_ref();
+            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get VM value representation: {}",
+                message
+            )))
         }
-    } else if let Ok(mut vm_object) = obj.extract::<PyRefMut<VMObject>>() {
-        Ok(vm_object.gc_ref.clone_ref())
-    } else {
-        Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-            "Expected a xlang VM type (VMInt, VMFloat, VMString, VMNull, VMBytes, VMKeyVal, VMNamed, VMTuple, VMWrapper, VMRange, VMObject) or WrappedPyFunction",
-        ))
-        
     }
 }
 
@@ -717,76 +1644,104 @@ pub(crate) fn xlang_gc_ref_to_py_object(
 ) -> PyResult<PyObject> {
     if gc_ref.isinstance::<XlangVMInt>() {
         let py_obj = VMInt {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMFloat>() {
         let py_obj = VMFloat {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMString>() {
         let py_obj = VMString {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMNull>() {
         let py_obj = VMNull {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMBytes>() {
         let py_obj = VMBytes {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMKeyVal>() {
         let py_obj = VMKeyVal {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMNamed>() {
         let py_obj = VMNamed {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMTuple>() {
         let py_obj = VMTuple {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMWrapper>() {
         // Handle VMWrapper case
         let py_obj = VMWrapper {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     } else if gc_ref.isinstance::<XlangVMRange>() {
         // Handle VMRange case
         let py_obj = VMRange {
-            gc_ref: gc_ref.clone_ref(),
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
             gc_system: gc_system_arc,
         };
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
-    } else {
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        let py_obj = VMBoolean {
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
+            gc_system: gc_system_arc,
+        };
+        Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
+    } else if gc_ref.isinstance::<XlangVMSet>() {
+        let py_obj = VMSet {
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
+            gc_system: gc_system_arc,
+        };
+        Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
+    } else if gc_ref.isinstance::<XlangVMInstructions>() {
+        let py_obj = VMInstructions {
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
+            gc_system: gc_system_arc,
+        };
+        Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
+    } else if gc_ref.isinstance::<XlangVMCLambdaInstruction>() {
+        let py_obj = VMCLambdaInstruction {
+            gc_ref: AliasRef::new(gc_ref.clone_ref()),
+            gc_system: gc_system_arc,
+        };
+        Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
+   } else if gc_ref.isinstance::<XlangVMLambda>() {
+        // 脚本返回的闭包可以直接在 Python 中调用
+        let py_obj = Lambda::wrap(gc_system_arc, gc_ref.clone_ref());
+        Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
+   } else {
         let py_obj = VMObject::wrap(gc_system_arc, gc_ref.clone_ref());
         Ok(Py::new(py, py_obj)?.into_pyobject(py)?.into())
     }
 }
 
-#[pyclass(unsendable)]
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMKeyVal {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
@@ -800,13 +1755,21 @@ impl VMKeyVal {
         let mut xlang_key_ref =
             extract_xlang_gc_ref_with_gc_arc(py_key.bind(py), gc.gc_system.clone())?;
         let mut xlang_value_ref =
-            extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), gc.gc_system.clone())?;
+            match extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), gc.gc_system.clone()) {
+                Ok(value_ref) => value_ref,
+                Err(e) => {
+                    xlang_key_ref.drop_ref();
+                    return Err(e);
+                }
+            };
 
         let xlang_kv = XlangVMKeyVal::new(&mut xlang_key_ref, &mut xlang_value_ref);
         let new_gc_ref = match gc.gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_kv),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
+            Err(e) => {
+                xlang_key_ref.drop_ref();
+                xlang_value_ref.drop_ref();
+                return Err(borrow_error("Failed to borrow GC system", e));
             }
         };
 
@@ -814,27 +1777,12 @@ impl VMKeyVal {
         xlang_value_ref.drop_ref();
 
         Ok(VMKeyVal {
-            gc_ref: new_gc_ref,
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: gc.gc_system.clone(),
         })
     }
 }
 
-impl GCRef for VMKeyVal {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
-    }
-}
-
 #[pymethods]
 impl VMKeyVal {
     #[new]
@@ -845,7 +1793,7 @@ impl VMKeyVal {
 
     #[pyo3(text_signature = "($self, py)")]
     fn get_key(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_kv = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
         xlang_gc_ref_to_py_object(&mut xlang_kv.key, self.gc_system.clone(), py)
     }
 
@@ -853,17 +1801,17 @@ impl VMKeyVal {
     fn set_key(&mut self, py_key: PyObject, py: Python) -> PyResult<()> {
         let mut new_key_ref =
             extract_xlang_gc_ref_with_gc_arc(py_key.bind(py), self.gc_system.clone())?;
-        let mut old_ref = self.gc_ref.as_type::<XlangVMKeyVal>().key.clone(); // Drop old key
-        self.gc_ref.as_type::<XlangVMKeyVal>().key = new_key_ref.clone(); // Assign new key (takes ownership)
-        self.gc_ref.get_traceable().add_reference(&mut new_key_ref);
-        self.gc_ref.get_traceable().remove_reference(&mut old_ref);
+        let mut old_ref = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>().key.clone(); // Drop old key
+        self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>().key = new_key_ref.clone(); // Assign new key (takes ownership)
+        self.gc_ref.get_mut()?.get_traceable().add_reference(&mut new_key_ref);
+        self.gc_ref.get_mut()?.get_traceable().remove_reference(&mut old_ref);
         new_key_ref.drop_ref(); // Drop the new key reference
         Ok(())
     }
 
     #[pyo3(text_signature = "($self, py)")]
     fn get_value(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_kv = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
         xlang_gc_ref_to_py_object(&mut xlang_kv.value, self.gc_system.clone(), py)
     }
 
@@ -871,18 +1819,19 @@ impl VMKeyVal {
     fn set_value(&mut self, py_value: PyObject, py: Python) -> PyResult<()> {
         let mut new_value_ref =
             extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), self.gc_system.clone())?;
-        let mut old_ref = self.gc_ref.as_type::<XlangVMKeyVal>().value.clone(); // Drop old key
-        self.gc_ref.as_type::<XlangVMKeyVal>().value = new_value_ref.clone(); // Assign new key (takes ownership)
+        let mut old_ref = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>().value.clone(); // Drop old key
+        self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>().value = new_value_ref.clone(); // Assign new key (takes ownership)
         self.gc_ref
+            .get_mut()?
             .get_traceable()
             .add_reference(&mut new_value_ref);
-        self.gc_ref.get_traceable().remove_reference(&mut old_ref);
+        self.gc_ref.get_mut()?.get_traceable().remove_reference(&mut old_ref);
         new_value_ref.drop_ref(); // Drop the new key reference
         Ok(())
     }
 
     fn __repr__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_kv = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
 
         let key_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.key, self.gc_system.clone(), py)?;
         let value_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.value, self.gc_system.clone(), py)?;
@@ -893,7 +1842,7 @@ impl VMKeyVal {
     }
 
     fn __str__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_kv = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
 
         let key_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.key, self.gc_system.clone(), py)?;
         let value_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.value, self.gc_system.clone(), py)?;
@@ -905,7 +1854,7 @@ impl VMKeyVal {
 
     #[pyo3(text_signature = "($self, py)")]
     fn clone(&mut self, _py: Python) -> PyResult<Self> {
-        let xlang_kv_orig = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv_orig = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
 
         // We need to create new Xlang objects for the cloned key and value if they are to be distinct
         // For now, assuming clone means new VMKeyVal wrapper with new XlangVMKeyVal containing *cloned* Xlang objects
@@ -924,38 +1873,40 @@ impl VMKeyVal {
         let new_xlang_kv = XlangVMKeyVal::new(&mut xlang_kv_orig.key, &mut xlang_kv_orig.value);
         let new_gc_ref = match self.gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(new_xlang_kv),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
 
         Ok(VMKeyVal {
-            gc_ref: new_gc_ref,
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: self.gc_system.clone(),
         })
     }
 
     #[pyo3(text_signature = "($self, py)")]
+    #[allow(clippy::wrong_self_convention)]
     fn to_py(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_kv = self.gc_ref.as_type::<XlangVMKeyVal>();
+        let xlang_kv = self.gc_ref.get_mut()?.as_type::<XlangVMKeyVal>();
         let key_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.key, self.gc_system.clone(), py)?;
         let value_obj = xlang_gc_ref_to_py_object(&mut xlang_kv.value, self.gc_system.clone(), py)?;
         let py_dict = PyDict::new(py);
         py_dict.set_item(key_obj, value_obj)?;
         Ok(py_dict.into())
     }
-}
 
-impl Drop for VMKeyVal {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMNamed {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
@@ -969,13 +1920,21 @@ impl VMNamed {
         let mut xlang_name_ref =
             extract_xlang_gc_ref_with_gc_arc(py_name.bind(py), gc.gc_system.clone())?;
         let mut xlang_value_ref =
-            extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), gc.gc_system.clone())?;
+            match extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), gc.gc_system.clone()) {
+                Ok(value_ref) => value_ref,
+                Err(e) => {
+                    xlang_name_ref.drop_ref();
+                    return Err(e);
+                }
+            };
 
         let xlang_named = XlangVMNamed::new(&mut xlang_name_ref, &mut xlang_value_ref);
         let new_gc_ref = match gc.gc_system.borrow_mut() {
             Ok(mut mut_gc_system_guard) => mut_gc_system_guard.new_object(xlang_named),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
+            Err(e) => {
+                xlang_name_ref.drop_ref();
+                xlang_value_ref.drop_ref();
+                return Err(borrow_error("Failed to borrow GC system", e));
             }
         };
 
@@ -983,27 +1942,12 @@ impl VMNamed {
         xlang_value_ref.drop_ref();
 
         Ok(VMNamed {
-            gc_ref: new_gc_ref,
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: gc.gc_system.clone(),
         })
     }
 }
 
-impl GCRef for VMNamed {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
-    }
-}
-
 #[pymethods]
 impl VMNamed {
     #[new]
@@ -1014,7 +1958,7 @@ impl VMNamed {
 
     #[pyo3(text_signature = "($self, py)")]
     fn get_name(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_named = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
         xlang_gc_ref_to_py_object(&mut xlang_named.key, self.gc_system.clone(), py)
     }
 
@@ -1022,16 +1966,16 @@ impl VMNamed {
     fn set_name(&mut self, py_name: PyObject, py: Python) -> PyResult<()> {
         let mut new_name_ref =
             extract_xlang_gc_ref_with_gc_arc(py_name.bind(py), self.gc_system.clone())?;
-        let mut old_ref = self.gc_ref.as_type::<XlangVMNamed>().key.clone(); // Drop old key
-        self.gc_ref.get_traceable().add_reference(&mut new_name_ref);
-        self.gc_ref.get_traceable().remove_reference(&mut old_ref);
+        let mut old_ref = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>().key.clone(); // Drop old key
+        self.gc_ref.get_mut()?.get_traceable().add_reference(&mut new_name_ref);
+        self.gc_ref.get_mut()?.get_traceable().remove_reference(&mut old_ref);
         new_name_ref.drop_ref(); // Drop the new key reference
         Ok(())
     }
 
     #[pyo3(text_signature = "($self, py)")]
     fn get_value(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_named = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
         xlang_gc_ref_to_py_object(&mut xlang_named.value, self.gc_system.clone(), py)
     }
 
@@ -1039,18 +1983,19 @@ impl VMNamed {
     fn set_value(&mut self, py_value: PyObject, py: Python) -> PyResult<()> {
         let mut new_value_ref =
             extract_xlang_gc_ref_with_gc_arc(py_value.bind(py), self.gc_system.clone())?;
-        let mut old_ref = self.gc_ref.as_type::<XlangVMNamed>().value.clone(); // Drop old key
-        self.gc_ref.as_type::<XlangVMNamed>().value = new_value_ref.clone(); // Assign new key (takes ownership)
+        let mut old_ref = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>().value.clone(); // Drop old key
+        self.gc_ref.get_mut()?.as_type::<XlangVMNamed>().value = new_value_ref.clone(); // Assign new key (takes ownership)
         self.gc_ref
+            .get_mut()?
             .get_traceable()
             .add_reference(&mut new_value_ref);
-        self.gc_ref.get_traceable().remove_reference(&mut old_ref);
+        self.gc_ref.get_mut()?.get_traceable().remove_reference(&mut old_ref);
         new_value_ref.drop_ref(); // Drop the new key reference
         Ok(())
     }
 
     fn __repr__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_named = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
 
         let name_obj = xlang_gc_ref_to_py_object(&mut xlang_named.key, self.gc_system.clone(), py)?;
         let value_obj =
@@ -1062,7 +2007,7 @@ impl VMNamed {
     }
 
     fn __str__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_named = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
 
         let name_obj = xlang_gc_ref_to_py_object(&mut xlang_named.key, self.gc_system.clone(), py)?;
         let value_obj =
@@ -1075,26 +2020,25 @@ impl VMNamed {
     #[pyo3(text_signature = "($self, py)")]
     fn clone(&mut self, _py: Python) -> PyResult<Self> {
         // Similar to VMKeyVal, this is a shallow clone of the structure for now.
-        let xlang_named_orig = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named_orig = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
 
         let new_xlang_named =
             XlangVMNamed::new(&mut xlang_named_orig.key, &mut xlang_named_orig.value);
         let new_gc_ref = match self.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(new_xlang_named),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
 
         Ok(VMNamed {
-            gc_ref: new_gc_ref,
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: self.gc_system.clone(),
         })
     }
 
     #[pyo3(text_signature = "($self, py)")]
+    #[allow(clippy::wrong_self_convention)]
     fn to_py(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_named = self.gc_ref.as_type::<XlangVMNamed>();
+        let xlang_named = self.gc_ref.get_mut()?.as_type::<XlangVMNamed>();
         let name_obj = xlang_gc_ref_to_py_object(&mut xlang_named.key, self.gc_system.clone(), py)?;
         let value_obj =
             xlang_gc_ref_to_py_object(&mut xlang_named.value, self.gc_system.clone(), py)?;
@@ -1102,254 +2046,215 @@ impl VMNamed {
         py_dict.set_item(name_obj, value_obj)?;
         Ok(py_dict.into())
     }
-}
 
-impl Drop for VMNamed {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMTuple {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
 impl VMTuple {
     fn create(gc: &mut GCSystem, py_values: Vec<PyObject>, py: Python) -> PyResult<Self> {
-        let mut xlang_refs_vec: Vec<XlangGCRef> = Vec::with_capacity(py_values.len());
-        for py_obj in py_values {
-            xlang_refs_vec.push(extract_xlang_gc_ref_with_gc_arc(
-                py_obj.bind(py),
-                gc.gc_system.clone(),
-            )?);
-        }
-
-        // XlangVMTuple::new expects &mut Vec<&mut GCRef>
-        // We have Vec<GCRef>. Need to convert.
-        let mut refs_for_xlang_constructor: Vec<&mut XlangGCRef> =
-            xlang_refs_vec.iter_mut().collect();
-
-        let xlang_tuple = XlangVMTuple::new(&mut refs_for_xlang_constructor);
-        let new_gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(xlang_tuple),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-
-        // Drop the GCRefs in xlang_refs_vec as XlangVMTuple::new clones them.
-        for mut r in xlang_refs_vec {
-            r.drop_ref();
-        }
+        let new_gc_ref = new_tuple_from_items(
+            py_values.iter().map(|py_obj| py_obj.bind(py).clone()),
+            &gc.gc_system,
+            "tuple creation",
+        )?;
 
         Ok(VMTuple {
-            gc_ref: new_gc_ref,
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: gc.gc_system.clone(),
         })
     }
 }
 
-impl GCRef for VMTuple {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
-    }
-}
-
-#[pymethods]
-impl VMTuple {
-    #[new]
-    #[pyo3(text_signature = "($cls, gc, values, py)")]
-    fn new(gc: &mut GCSystem, values: Vec<PyObject>, py: Python) -> PyResult<Self> {
-        VMTuple::create(gc, values, py)
-    }
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+    ],
+    impl VMTuple {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, values, py)")]
+        fn new(gc: &mut GCSystem, values: Vec<PyObject>, py: Python) -> PyResult<Self> {
+            VMTuple::create(gc, values, py)
+        }
 
-    fn __len__(&self) -> usize {
-        self.gc_ref.as_const_type::<XlangVMTuple>().values.len()
-    }
+        fn __len__(&self) -> PyResult<usize> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMTuple>().values.len())
+        }
 
-    // get_item is complex due to Python's rich indexing. For now, simple usize index.
-    fn __getitem__(&mut self, idx: usize, py: Python) -> PyResult<PyObject> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        if idx < xlang_tuple.values.len() {
-            xlang_gc_ref_to_py_object(&mut xlang_tuple.values[idx], self.gc_system.clone(), py)
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(
-                "Tuple index out of range",
-            ))
+        // get_item is complex due to Python's rich indexing. For now, simple usize index.
+        fn __getitem__(&mut self, idx: usize, py: Python) -> PyResult<PyObject> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            if idx < xlang_tuple.values.len() {
+                xlang_gc_ref_to_py_object(&mut xlang_tuple.values[idx], self.gc_system.clone(), py)
+            } else {
+                Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(
+                    "Tuple index out of range",
+                ))
+            }
         }
-    }
 
-    fn __getattr__(&mut self, attr: &str, py: Python) -> PyResult<PyObject> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        for item_ref in &mut xlang_tuple.values {
-            if item_ref.isinstance::<XlangVMNamed>() {
-                let xlang_named = item_ref.as_type::<XlangVMNamed>();
-                if !xlang_named.key.isinstance::<XlangVMString>() {
-                    continue;
-                }
-                let xlang_key = xlang_named.key.as_const_type::<XlangVMString>();
-                let key_str = xlang_key.value.as_str();
-                if key_str == attr {
-                    return xlang_gc_ref_to_py_object(
-                        &mut xlang_named.value,
-                        self.gc_system.clone(),
-                        py,
-                    );
-                }
-            } else if item_ref.isinstance::<XlangVMKeyVal>() {
-                let xlang_kv = item_ref.as_type::<XlangVMKeyVal>();
-                if !xlang_kv.key.isinstance::<XlangVMString>() {
-                    continue;
-                }
-                let xlang_key = xlang_kv.key.as_const_type::<XlangVMString>();
-                let key_str = xlang_key.value.as_str();
-                if key_str == attr {
-                    return xlang_gc_ref_to_py_object(
-                        &mut xlang_kv.value,
-                        self.gc_system.clone(),
-                        py,
-                    );
+        fn __getattr__(&mut self, attr: &str, py: Python) -> PyResult<PyObject> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            for item_ref in &mut xlang_tuple.values {
+                if item_ref.isinstance::<XlangVMNamed>() {
+                    let xlang_named = item_ref.as_type::<XlangVMNamed>();
+                    if !xlang_named.key.isinstance::<XlangVMString>() {
+                        continue;
+                    }
+                    let xlang_key = xlang_named.key.as_const_type::<XlangVMString>();
+                    let key_str = xlang_key.value.as_str();
+                    if key_str == attr {
+                        return xlang_gc_ref_to_py_object(
+                            &mut xlang_named.value,
+                            self.gc_system.clone(),
+                            py,
+                        );
+                    }
+                } else if item_ref.isinstance::<XlangVMKeyVal>() {
+                    let xlang_kv = item_ref.as_type::<XlangVMKeyVal>();
+                    if !xlang_kv.key.isinstance::<XlangVMString>() {
+                        continue;
+                    }
+                    let xlang_key = xlang_kv.key.as_const_type::<XlangVMString>();
+                    let key_str = xlang_key.value.as_str();
+                    if key_str == attr {
+                        return xlang_gc_ref_to_py_object(
+                            &mut xlang_kv.value,
+                            self.gc_system.clone(),
+                            py,
+                        );
+                    }
                 }
             }
+            Err(PyErr::new::<pyo3::exceptions::PyAttributeError, _>(
+                format!("Attribute {} not found in tuple", attr),
+            ))
         }
-        Err(PyErr::new::<pyo3::exceptions::PyAttributeError, _>(
-            format!("Attribute {} not found in tuple", attr),
-        ))
-    }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_list(&mut self, py: Python) -> PyResult<Vec<PyObject>> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        let mut py_list = Vec::with_capacity(xlang_tuple.values.len());
-        for item_ref in &mut xlang_tuple.values {
-            py_list.push(xlang_gc_ref_to_py_object(
-                item_ref,
-                self.gc_system.clone(),
-                py,
-            )?);
+        #[pyo3(text_signature = "($self, py)")]
+        #[allow(clippy::wrong_self_convention)]
+        fn to_list(&mut self, py: Python) -> PyResult<Vec<PyObject>> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            let mut py_list = Vec::with_capacity(xlang_tuple.values.len());
+            for item_ref in &mut xlang_tuple.values {
+                py_list.push(xlang_gc_ref_to_py_object(
+                    item_ref,
+                    self.gc_system.clone(),
+                    py,
+                )?);
+            }
+            Ok(py_list)
         }
-        Ok(py_list)
-    }
 
-    fn __repr__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        let mut reprs = Vec::new();
-        for item_ref in &mut xlang_tuple.values {
-            let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
-            reprs.push(item_obj.bind(py).repr()?.extract::<String>()?);
-        }
-        if reprs.len() == 1 {
-            Ok(format!("VMTuple(({},))", reprs.join(", ")))
-        } else {
-            Ok(format!("VMTuple(({}))", reprs.join(", ")))
+        fn __repr__(&mut self, py: Python) -> PyResult<String> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            let mut reprs = Vec::new();
+            for item_ref in &mut xlang_tuple.values {
+                let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
+                reprs.push(item_obj.bind(py).repr()?.extract::<String>()?);
+            }
+            if reprs.len() == 1 {
+                Ok(format!("VMTuple(({},))", reprs.join(", ")))
+            } else {
+                Ok(format!("VMTuple(({}))", reprs.join(", ")))
+            }
         }
-    }
 
-    fn __str__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        let mut str_items = Vec::new();
-        for item_ref in &mut xlang_tuple.values {
-            let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
-            str_items.push(item_obj.bind(py).str()?.extract::<String>()?);
+        fn __str__(&mut self, py: Python) -> PyResult<String> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            let mut str_items = Vec::new();
+            for item_ref in &mut xlang_tuple.values {
+                let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
+                str_items.push(item_obj.bind(py).str()?.extract::<String>()?);
+            }
+
+            if str_items.len() == 1 {
+                Ok(format!("({},)", str_items.join(", ")))
+            } else {
+                Ok(format!("({})", str_items.join(", ")))
+            }
         }
 
-        if str_items.len() == 1 {
-            Ok(format!("({},)", str_items.join(", ")))
-        } else {
-            Ok(format!("({})", str_items.join(", ")))
+        #[pyo3(text_signature = "($self, py)")]
+        fn clone(&mut self, _py: Python) -> PyResult<Self> {
+            // This will be a shallow clone of the tuple structure, elements are shared.
+            // For a deep clone, each element would need to be cloned.
+            let xlang_tuple_orig = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            let new_tuple = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(XlangVMTuple::new(
+                    &mut xlang_tuple_orig.values.iter_mut().collect(),
+                )),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+
+            Ok(VMTuple {
+                gc_ref: AliasRef::new(new_tuple),
+                gc_system: self.gc_system.clone(),
+            })
         }
-    }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn clone(&mut self, _py: Python) -> PyResult<Self> {
-        // This will be a shallow clone of the tuple structure, elements are shared.
-        // For a deep clone, each element would need to be cloned.
-        let xlang_tuple_orig = self.gc_ref.as_type::<XlangVMTuple>();
-        let new_tuple = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMTuple::new(
-                &mut xlang_tuple_orig.values.iter_mut().collect(),
-            )),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
+        #[pyo3(text_signature = "($self, py)")]
+        #[allow(clippy::wrong_self_convention)]
+        fn to_py(&mut self, py: Python) -> PyResult<PyObject> {
+            let xlang_tuple = self.gc_ref.get_mut()?.as_type::<XlangVMTuple>();
+            let py_tuple = PyList::empty(py);
+            for item_ref in &mut xlang_tuple.values {
+                let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
+                py_tuple.append(item_obj)?;
             }
-        };
+            Ok(py_tuple.into())
+        }
 
-        Ok(VMTuple {
-            gc_ref: new_tuple,
-            gc_system: self.gc_system.clone(),
-        })
-    }
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_tuple = self.gc_ref.as_type::<XlangVMTuple>();
-        let py_tuple = PyList::empty(py);
-        for item_ref in &mut xlang_tuple.values {
-            let item_obj = xlang_gc_ref_to_py_object(item_ref, self.gc_system.clone(), py)?;
-            py_tuple.append(item_obj)?;
+        fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+            operators::contains(self.gc_ref.get()?, &self.gc_system, item)
         }
-        Ok(py_tuple.into())
-    }
-}
 
-impl Drop for VMTuple {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMWrapper {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 impl VMWrapper {
-    fn create(gc: &mut GCSystem, value: &mut XlangGCRef) -> Self {
+    fn create(gc: &mut GCSystem, value: &mut XlangGCRef) -> PyResult<Self> {
         let gc_ref = match gc.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XlangVMWrapper::new(value)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        VMWrapper {
-            gc_ref,
+        Ok(VMWrapper {
+            gc_ref: AliasRef::new(gc_ref),
             gc_system: gc.gc_system.clone(),
-        }
-    }
-}
-impl GCRef for VMWrapper {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
-    }
-}
-impl Drop for VMWrapper {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+        })
     }
 }
-
 #[pymethods]
 impl VMWrapper {
     #[new]
@@ -1358,12 +2263,12 @@ impl VMWrapper {
         let mut xlang_ref = extract_xlang_gc_ref_with_gc_arc(value.bind(py), gc.gc_system.clone())?;
         let wrapped = VMWrapper::create(gc, &mut xlang_ref);
         xlang_ref.drop_ref(); // Drop the cloned ref
-        Ok(wrapped)
+        wrapped
     }
 
     #[pyo3(text_signature = "($self, py)")]
     fn get_value(&mut self, py: Python) -> PyResult<PyObject> {
-        let xlang_wrapper = self.gc_ref.as_type::<XlangVMWrapper>();
+        let xlang_wrapper = self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>();
         xlang_gc_ref_to_py_object(&mut xlang_wrapper.value_ref, self.gc_system.clone(), py)
     }
 
@@ -1371,24 +2276,25 @@ impl VMWrapper {
     fn set_value(&mut self, value: PyObject, py: Python) -> PyResult<()> {
         let mut new_value_ref =
             extract_xlang_gc_ref_with_gc_arc(value.bind(py), self.gc_system.clone())?;
-        let mut old_ref = self.gc_ref.as_type::<XlangVMWrapper>().value_ref.clone(); // Drop old key
-        self.gc_ref.as_type::<XlangVMWrapper>().value_ref = new_value_ref.clone(); // Assign new key (takes ownership)
+        let mut old_ref = self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>().value_ref.clone(); // Drop old key
+        self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>().value_ref = new_value_ref.clone(); // Assign new key (takes ownership)
         self.gc_ref
+            .get_mut()?
             .get_traceable()
             .add_reference(&mut new_value_ref);
-        self.gc_ref.get_traceable().remove_reference(&mut old_ref);
+        self.gc_ref.get_mut()?.get_traceable().remove_reference(&mut old_ref);
         new_value_ref.drop_ref(); // Drop the new key reference
         Ok(())
     }
     fn __repr__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_wrapper = self.gc_ref.as_type::<XlangVMWrapper>();
+        let xlang_wrapper = self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>();
         let value_obj =
             xlang_gc_ref_to_py_object(&mut xlang_wrapper.value_ref, self.gc_system.clone(), py)?;
         let value_repr = value_obj.bind(py).repr()?.extract::<String>()?;
         Ok(format!("VMWrapper({})", value_repr))
     }
     fn __str__(&mut self, py: Python) -> PyResult<String> {
-        let xlang_wrapper = self.gc_ref.as_type::<XlangVMWrapper>();
+        let xlang_wrapper = self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>();
         let value_obj =
             xlang_gc_ref_to_py_object(&mut xlang_wrapper.value_ref, self.gc_system.clone(), py)?;
         let value_str = value_obj.bind(py).str()?.extract::<String>()?;
@@ -1399,142 +2305,407 @@ impl VMWrapper {
     fn clone(&mut self, _py: Python) -> PyResult<Self> {
         // This will be a shallow clone of the wrapper structure, elements are shared.
         // For a deep clone, each element would need to be cloned.
-        let xlang_wrapper_origin = self.gc_ref.as_type::<XlangVMWrapper>();
+        let xlang_wrapper_origin = self.gc_ref.get_mut()?.as_type::<XlangVMWrapper>();
         let new_wrapper = match self.gc_system.borrow_mut() {
             Ok(mut gc_system) => {
                 gc_system.new_object(XlangVMWrapper::new(&mut xlang_wrapper_origin.value_ref))
             }
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
 
         Ok(VMWrapper {
-            gc_ref: new_wrapper,
+            gc_ref: AliasRef::new(new_wrapper),
+            gc_system: self.gc_system.clone(),
+        })
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+    }
+}
+
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMRange {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
+impl VMRange {
+    fn create(gc: &mut GCSystem, start: i64, end: i64) -> PyResult<Self> {
+        let gc_ref = match gc.gc_system.borrow_mut() {
+            Ok(mut gc_system) => gc_system.new_object(XlangVMRange::new(start, end)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        Ok(VMRange {
+            gc_ref: AliasRef::new(gc_ref),
+            gc_system: gc.gc_system.clone(),
+        })
+    }
+}
+
+#[pymethods]
+impl VMRange {
+    #[new]
+    #[pyo3(text_signature = "($cls, gc, start, end)")]
+    fn new(gc: &mut GCSystem, start: i64, end: i64) -> PyResult<Self> {
+        VMRange::create(gc, start, end)
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn get_start(&self) -> PyResult<i64> {
+        Ok(self.gc_ref.get()?.as_const_type::<XlangVMRange>().start)
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn get_end(&self) -> PyResult<i64> {
+        Ok(self.gc_ref.get()?.as_const_type::<XlangVMRange>().end)
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn get_key(&self) -> PyResult<i64> {
+        self.get_start()
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn get_value(&self) -> PyResult<i64> {
+        self.get_end()
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let start = self.get_start()?;
+        let end = self.get_end()?;
+        Ok(format!("VMRange({}, {})", start, end))
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        let start = self.get_start()?;
+        let end = self.get_end()?;
+        Ok(format!("VMRange({}, {})", start, end))
+    }
+
+    // end 小于 start 的区间视为空区间，与虚拟机不产生任何迭代值一致
+    fn __len__(&self) -> PyResult<usize> {
+        match self.get_end()?.checked_sub(self.get_start()?) {
+            Some(len) => Ok(len.max(0) as usize),
+            None => Err(PyOverflowError::new_err(
+                "VMRange length does not fit in a 64-bit integer",
+            )),
+        }
+    }
+
+    fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.to_py(py)?.bind(py).try_iter()?.into_any().unbind())
+    }
+
+    fn __reversed__(&self, py: Python) -> PyResult<PyObject> {
+        let reversed = self.to_py(py)?.bind(py).call_method0("__reversed__")?;
+        Ok(reversed.unbind())
+    }
+
+    // 与迭代结果一致，不包含 end（虚拟机的 in 运算符包含 end）
+    fn __contains__(&self, item: &Bound<'_, PyAny>, py: Python) -> PyResult<bool> {
+        let float = if let Ok(float) = item.downcast::<PyFloat>() {
+            Some(float.value())
+        } else if let Ok(vm_float) = item.extract::<PyRef<VMFloat>>() {
+            Some(vm_float.get_value()?)
+        } else {
+            None
+        };
+        let value = if let Some(float) = float {
+            // 带小数部分的浮点数不可能等于区间中的整数
+            match integral_float(float) {
+                Some(value) => value,
+                None => return Ok(false),
+            }
+        } else if let Ok(value) = item.extract::<i64>() {
+            value
+        } else if let Ok(vm_int) = item.extract::<PyRef<VMInt>>() {
+            vm_int.get_value()?
+        } else {
+            // 其他类型与 Python range 一样逐个比较相等
+            return self.to_py(py)?.bind(py).contains(item);
+        };
+        Ok(self.get_start()? <= value && value < self.get_end()?)
+    }
+
+    fn __getitem__(&self, index: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        let len = self.__len__()?;
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let indices = slice.indices(len as isize)?;
+            if indices.step != 1 {
+                return Err(PyValueError::new_err(
+                    "VMRange does not support stepped slices",
+                ));
+            }
+            let start = self.get_start()?.checked_add(indices.start as i64);
+            let end = start.and_then(|start| start.checked_add(indices.slicelength as i64));
+            let (start, end) = match (start, end) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return Err(PyOverflowError::new_err("VMRange slice bounds overflow")),
+            };
+            let new_gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(XlangVMRange::new(start, end)),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            let vm_range = VMRange {
+                gc_ref: AliasRef::new(new_gc_ref),
+                gc_system: self.gc_system.clone(),
+            };
+            return Ok(Py::new(py, vm_range)?.into_any());
+        }
+        let mut i = index.extract::<isize>()?;
+        if i < 0 {
+            i += len as isize;
+        }
+        if i < 0 || i >= len as isize {
+            return Err(PyIndexError::new_err("range index out of range"));
+        }
+        match self.get_start()?.checked_add(i as i64) {
+            Some(value) => Ok(value.into_pyobject(py)?.into_any().unbind()),
+            None => Err(PyOverflowError::new_err("VMRange index overflow")),
+        }
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn clone(&self) -> PyResult<Self> {
+        let new_gc_ref = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => {
+                gc_system.new_object(XlangVMRange::new(self.get_start()?, self.get_end()?))
+            }
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        Ok(VMRange {
+            gc_ref: AliasRef::new(new_gc_ref),
             gc_system: self.gc_system.clone(),
         })
     }
+
+    #[pyo3(text_signature = "($self, py)")]
+    fn to_py(&self, py: Python) -> PyResult<PyObject> {
+        let start = self.get_start()?;
+        let end = self.get_end()?;
+
+        // 创建一个 Python range 对象
+        let range_module = py.import("builtins")?;
+        let range_fn = range_module.getattr("range")?;
+        let py_range = range_fn.call1((start, end))?;
+
+        Ok(py_range.into())
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<isize> {
+        operators::hash_value(self.gc_ref.get()?, py)
+    }
 }
 
-#[pyclass(unsendable)]
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
-struct VMRange {
-    gc_ref: XlangGCRef,
+struct VMBoolean {
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
-impl VMRange {
-    fn create(gc: &mut GCSystem, start: i64, end: i64) -> Self {
+
+impl VMBoolean {
+    fn create(gc: &mut GCSystem, value: bool) -> PyResult<Self> {
         let gc_ref = match gc.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XlangVMRange::new(start, end)),
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+            Ok(mut gc_system) => gc_system.new_object(XlangVMBoolean::new(value)),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
         };
-        VMRange {
-            gc_ref,
+        Ok(VMBoolean {
+            gc_ref: AliasRef::new(gc_ref),
             gc_system: gc.gc_system.clone(),
-        }
+        })
     }
 }
 
-impl GCRef for VMRange {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
-    }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+vm_pymethods! {
+    binary_ops: [
+        (__and__, __rand__, try_and_as_vmobject),
+        (__or__, __ror__, try_or_as_vmobject),
+    ],
+    unary_ops: [
+        (__invert__, try_not_as_vmobject),
+    ],
+    impl VMBoolean {
+        #[new]
+        #[pyo3(text_signature = "($cls, gc, value)")]
+        fn new(gc: &mut GCSystem, value: bool) -> PyResult<Self> {
+            VMBoolean::create(gc, value)
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn get_value(&self) -> PyResult<bool> {
+            Ok(self.gc_ref.get()?.as_const_type::<XlangVMBoolean>().value)
+        }
+
+        #[pyo3(text_signature = "($self, value)")]
+        fn set_value(&mut self, value: bool) -> PyResult<()> {
+            self.gc_ref.get_mut()?.as_type::<XlangVMBoolean>().value = value;
+            Ok(())
+        }
+
+        fn __repr__(&self) -> PyResult<String> {
+            Ok(format!("VMBoolean({})", if self.get_value()? { "True" } else { "False" }))
+        }
+
+        fn __str__(&self) -> PyResult<String> {
+            Ok(self.get_value()?.to_string())
+        }
+
+        #[pyo3(text_signature = "($self)")]
+        fn clone(&self) -> PyResult<Self> {
+            let gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(XlangVMBoolean::new(self.get_value()?)),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMBoolean {
+                gc_ref: AliasRef::new(gc_ref),
+                gc_system: self.gc_system.clone(),
+            })
+        }
+
+        #[pyo3(text_signature = "($self, py)")]
+        fn to_py(&self, py: Python) -> PyResult<PyObject> {
+            Ok(PyBool::new(py, self.get_value()?).to_owned().into_any().unbind())
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            self.get_value()
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __hash__(&self, py: Python) -> PyResult<isize> {
+            operators::hash_value(self.gc_ref.get()?, py)
+        }
     }
 }
-impl Drop for VMRange {
-    fn drop(&mut self) {
-        self.gc_ref.drop_ref();
-    }
+
+
+// 集合由原始集合与过滤 lambda 组成，过滤需要虚拟机执行，这里只暴露两者
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMSet {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
 #[pymethods]
-impl VMRange {
-    #[new]
-    #[pyo3(text_signature = "($cls, gc, start, end)")]
-    fn new(gc: &mut GCSystem, start: i64, end: i64) -> Self {
-        VMRange::create(gc, start, end)
+impl VMSet {
+    #[pyo3(text_signature = "($self, py)")]
+    fn get_collection(&mut self, py: Python) -> PyResult<PyObject> {
+        let set = self.gc_ref.get_mut()?.as_type::<XlangVMSet>();
+        xlang_gc_ref_to_py_object(&mut set.collection, self.gc_system.clone(), py)
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_start(&self) -> i64 {
-        self.gc_ref.as_const_type::<XlangVMRange>().start
+    #[pyo3(text_signature = "($self, py)")]
+    fn get_filter(&mut self, py: Python) -> PyResult<PyObject> {
+        let set = self.gc_ref.get_mut()?.as_type::<XlangVMSet>();
+        xlang_gc_ref_to_py_object(&mut set.filter, self.gc_system.clone(), py)
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_end(&self) -> i64 {
-        self.gc_ref.as_const_type::<XlangVMRange>().end
+    fn __repr__(&self) -> PyResult<String> {
+        vm_repr(self.gc_ref.get()?)
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn get_key(&self) -> i64 {
-        self.get_start()
+    fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+        operators::contains(self.gc_ref.get()?, &self.gc_system, item)
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
     }
+}
+
+
+// 编译后的指令包，作为 lambda 的函数体出现
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMInstructions {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
 
+#[pymethods]
+impl VMInstructions {
+    // 指令包中所有函数签名
     #[pyo3(text_signature = "($self)")]
-    fn get_value(&self) -> i64 {
-        self.get_end()
+    fn signatures(&self) -> PyResult<Vec<String>> {
+        let instructions = self.gc_ref.get()?.as_const_type::<XlangVMInstructions>();
+        let mut signatures: Vec<String> = instructions
+            .vm_instructions_package
+            .get_table()
+            .keys()
+            .cloned()
+            .collect();
+        signatures.sort();
+        Ok(signatures)
     }
 
     fn __repr__(&self) -> PyResult<String> {
-        let start = self.get_start();
-        let end = self.get_end();
-        Ok(format!("VMRange({}, {})", start, end))
+        vm_repr(self.gc_ref.get()?)
     }
 
-    fn __str__(&self) -> PyResult<String> {
-        let start = self.get_start();
-        let end = self.get_end();
-        Ok(format!("VMRange({}, {})", start, end))
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
     }
 
-    fn __len__(&self) -> usize {
-        (self.get_end() - self.get_start()) as usize
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
     }
+}
 
-    #[pyo3(text_signature = "($self)")]
-    fn clone(&self) -> Self {
-        let new_gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => {
-                gc_system.new_object(XlangVMRange::new(self.get_start(), self.get_end()))
-            }
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
-        };
-        VMRange {
-            gc_ref: new_gc_ref,
-            gc_system: self.gc_system.clone(),
-        }
-    }
 
-    #[pyo3(text_signature = "($self, py)")]
-    fn to_py(&self, py: Python) -> PyResult<PyObject> {
-        let start = self.get_start();
-        let end = self.get_end();
+// 通过 C ABI 加载的原生函数体
+#[pyclass(extends=VMValue, unsendable)]
+#[derive(Clone)]
+struct VMCLambdaInstruction {
+    gc_ref: AliasRef,
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+}
 
-        // 创建一个 Python range 对象
-        let range_module = py.import("builtins")?;
-        let range_fn = range_module.getattr("range")?;
-        let py_range = range_fn.call1((start, end))?;
+#[pymethods]
+impl VMCLambdaInstruction {
+    fn __repr__(&self) -> PyResult<String> {
+        vm_repr(self.gc_ref.get()?)
+    }
 
-        Ok(py_range.into())
+    fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+    }
+
+    fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+        operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
     }
 }
 
-#[pyclass(unsendable)]
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 struct VMObject {
-    gc_ref: XlangGCRef,
+    gc_ref: AliasRef,
     gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
 }
 
@@ -1544,128 +2715,525 @@ impl VMObject {
         xlang_object: XlangGCRef,
     ) -> Self {
        VMObject {
-            gc_ref: xlang_object,
+            gc_ref: AliasRef::new(xlang_object),
             gc_system: gc.clone(),
         }
     }    
 }
 
-impl GCRef for VMObject {
-    fn get_ref(&self) -> &XlangGCRef {
-        &self.gc_ref
-    }
-    fn get_mut_ref(&mut self) -> &mut XlangGCRef {
-        &mut self.gc_ref
-    }
-    fn clone_ref(&mut self) -> XlangGCRef {
-        self.gc_ref.clone_ref()
+vm_pymethods! {
+    binary_ops: [
+        (__add__, __radd__, operators::checked_add_as_vmobject),
+        (__sub__, __rsub__, operators::checked_sub_as_vmobject),
+        (__mul__, __rmul__, operators::checked_mul_as_vmobject),
+        (__truediv__, __rtruediv__, try_div_as_vmobject),
+        (__and__, __rand__, try_and_as_vmobject),
+        (__or__, __ror__, try_or_as_vmobject),
+        (__xor__, __rxor__, try_xor_as_vmobject),
+        (__lshift__, __rlshift__, operators::checked_shift_left_as_vmobject),
+        (__rshift__, __rrshift__, operators::checked_shift_right_as_vmobject),
+    ],
+    unary_ops: [
+        (__neg__, operators::try_negate_as_vmobject),
+        (__invert__, try_not_as_vmobject),
+    ],
+    compare_ops: [
+        (__lt__, try_less_than_as_vmobject, false),
+        (__le__, try_greater_than_as_vmobject, true),
+        (__gt__, try_greater_than_as_vmobject, false),
+        (__ge__, try_less_than_as_vmobject, true),
+    ],
+    impl VMObject {
+        #[pyo3(text_signature = "($self, py)")]
+        fn get_value(&self, py: Python) -> PyResult<PyObject> {
+            let mut self_ref = self.gc_ref.get()?.clone();
+            match try_value_of_as_vmobject(&mut self_ref) {
+                Ok(value) => xlang_gc_ref_to_py_object(value, self.gc_system.clone(), py),
+                Err(mut e) => {
+                    e.consume_ref();
+                    match vm_type_name(self.gc_ref.get()?) {
+                        "int" | "float" | "string" | "bool" | "null" | "bytes" | "range" => {
+                            xlang_gc_ref_to_py_object(
+                                &mut self.gc_ref.get()?.clone(),
+                                self.gc_system.clone(),
+                                py,
+                            )?
+                            .call_method0(py, "to_py")
+                        }
+                        type_name => Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                            "VM value of type '{}' has no value",
+                            type_name
+                        ))),
+                    }
+                }
+            }
+        }
+
+        fn __getattr__(&self, name: &str, py: Python) -> PyResult<PyObject> {
+            let mut attr_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => gc_system.new_object(XlangVMString::new(name)),
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            let mut self_ref = self.gc_ref.get()?.clone();
+            let result = match try_get_attr_as_vmobject(&mut self_ref, &mut attr_ref) {
+                Ok(value) => xlang_gc_ref_to_py_object(value, self.gc_system.clone(), py),
+                Err(mut e) => {
+                    e.consume_ref();
+                    Err(PyErr::new::<pyo3::exceptions::PyAttributeError, _>(format!(
+                        "VM value has no attribute '{}'",
+                        name
+                    )))
+                }
+            };
+            attr_ref.drop_ref();
+            result
+        }
+
+        fn __getitem__(&self, index: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            let mut index_ref = extract_xlang_gc_ref_with_gc_arc(index, self.gc_system.clone())?;
+            let mut self_ref = self.gc_ref.get()?.clone();
+            let result = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => {
+                    try_index_of_as_vmobject(&mut self_ref, &mut index_ref, &mut gc_system)
+                }
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            index_ref.drop_ref();
+            match result {
+                Ok(mut value) => {
+                    let py_object =
+                        xlang_gc_ref_to_py_object(&mut value, self.gc_system.clone(), py);
+                    value.drop_ref();
+                    py_object
+                }
+                Err(e) => Err(operators::vm_variable_error_to_pyerr(e)),
+            }
+        }
+
+        fn __len__(&self) -> PyResult<usize> {
+            try_length_of_as_vmobject(&mut self.gc_ref.get()?.clone())
+                .map_err(operators::vm_variable_error_to_pyerr)
+        }
+
+        fn __repr__(&mut self) -> PyResult<String> {
+            let repr = try_repr_vmobject(self.gc_ref.get_mut()?, None);
+            match repr {
+                Ok(r) => Ok(r),
+                Err(_) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    "Failed to get VMObject representation",
+                )),
+            }
+        }
+
+        fn __str__(&mut self) -> PyResult<String> {
+            let str = try_to_string_vmobject(self.gc_ref.get_mut()?, None);
+            match str {
+                Ok(s) => Ok(s),
+                Err(_) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    "Failed to get VMObject string representation",
+                )),
+            }
+        }
+
+        fn clone(&mut self) -> PyResult<Self> {
+            let new_gc_ref = match self.gc_system.borrow_mut() {
+                Ok(mut gc_system) => {
+                    match try_copy_as_vmobject(self.gc_ref.get_mut()?, &mut gc_system) {
+                        Ok(new_ref) => new_ref,
+                        Err(e) => return Err(operators::vm_variable_error_to_pyerr(e)),
+                    }
+                },
+                Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+            };
+            Ok(VMObject {
+                gc_ref: AliasRef::new(new_gc_ref),
+                gc_system: self.gc_system.clone(),
+            })
+        }
+
+        fn __mod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __rmod__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::modulo(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __pow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, false, py)
+        }
+
+        fn __rpow__(
+            &self,
+            other: &Bound<'_, PyAny>,
+            modulo: &Bound<'_, PyAny>,
+            py: Python,
+        ) -> PyResult<PyObject> {
+            operators::power(self.gc_ref.get()?, &self.gc_system, other, modulo, true, py)
+        }
+
+        fn __bool__(&self) -> PyResult<bool> {
+            Ok(operators::truthiness(self.gc_ref.get()?))
+        }
+
+        fn __int__(&self) -> PyResult<i64> {
+            operators::to_int(self.gc_ref.get()?)
+        }
+
+        fn __float__(&self) -> PyResult<f64> {
+            operators::to_float(self.gc_ref.get()?)
+        }
+
+        fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+            operators::contains(self.gc_ref.get()?, &self.gc_system, item)
+        }
+
+        fn __eq__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, false, py)
+        }
+
+        fn __ne__(&self, other: &Bound<'_, PyAny>, py: Python) -> PyResult<PyObject> {
+            operators::equals(self.gc_ref.get()?, &self.gc_system, other, true, py)
+        }
+
+        fn __hash__(&self, py: Python) -> PyResult<isize> {
+            operators::hash_value(self.gc_ref.get()?, py)
+        }
     }
-    fn drop_ref(&mut self) {
-        self.gc_ref.drop_ref();
+}
+
+impl GCSystem {
+    fn from_heap(gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>) -> Self {
+        heap::add_handle(&gc_system);
+        let roots = heap::roots_for_wrapper(&gc_system);
+        GCSystem { gc_system, roots }
     }
 }
-impl Drop for VMObject {
+
+impl Drop for GCSystem {
     fn drop(&mut self) {
-        self.gc_ref.drop_ref();
+        if !heap::remove_handle(&self.gc_system) {
+            return;
+        }
+        // 默认堆仍被模块持有，不算泄漏
+        let is_default = DEFAULT_GC.with(|default_gc| {
+            default_gc
+                .borrow()
+                .as_ref()
+                .is_some_and(|default| default.get_inner() == self.gc_system.get_inner())
+        });
+        if is_default {
+            return;
+        }
+        let wrapped = match self.gc_system.borrow() {
+            Ok(gc_system) => heap::wrapped_objects(&gc_system),
+            Err(_) => return,
+        };
+        if wrapped.is_empty() {
+            return;
+        }
+        let message = format!(
+            "GCSystem dropped while {} VM objects are still referenced by Python wrappers: {}",
+            wrapped.values().sum::<usize>(),
+            heap::format_type_counts(&wrapped)
+        );
+        Python::with_gil(|py| {
+            let category = py.get_type::<PyResourceWarning>();
+            if let Err(e) = PyErr::warn(py, &category, &CString::new(message).unwrap(), 1) {
+                e.write_unraisable(py, None);
+            }
+        });
     }
 }
 
 #[pymethods]
-impl VMObject {
-    fn __repr__(&mut self) -> PyResult<String> {
-        let repr = try_repr_vmobject(&mut self.gc_ref, None);
-        match repr {
-            Ok(r) => Ok(r),
-            Err(_) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Failed to get VMObject representation",
-            )),
+impl GCSystem {
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        if let Some(roots) = &self.roots {
+            visit.call(roots)?;
         }
+        Ok(())
     }
 
-    fn __str__(&mut self) -> PyResult<String> {
-        let str = try_to_string_vmobject(&mut self.gc_ref, None);
-        match str {
-            Ok(s) => Ok(s),
-            Err(_) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Failed to get VMObject string representation",
-            )),
+    fn __clear__(&mut self) {
+        self.roots = None;
+    }
+
+    // warn_on_leak 为 True 时，最后一个 GCSystem 对象释放时如果仍有值被 Python 包装对象引用，发出 ResourceWarning
+    #[new]
+    #[pyo3(signature = (warn_on_leak=false))]
+    fn new(warn_on_leak: bool) -> Self {
+        let gc = GCSystem::from_heap(new_gc_system());
+        heap::set_warn_on_leak(&gc.gc_system, warn_on_leak);
+        gc
+    }
+
+    #[getter]
+    fn get_warn_on_leak(&self) -> bool {
+        heap::warn_on_leak(&self.gc_system)
+    }
+
+    #[setter]
+    fn set_warn_on_leak(&self, warn: bool) {
+        heap::set_warn_on_leak(&self.gc_system, warn);
+    }
+
+    // with gc.leak_check(): 退出时回收并检查新增的存活对象
+    #[pyo3(text_signature = "($self)")]
+    fn leak_check(&self) -> LeakCheck {
+        LeakCheck::new(&self.gc_system)
+    }
+
+    // with gc.scope(): 块内创建的包装对象在退出时释放，也可用作装饰器
+    #[pyo3(text_signature = "($self)")]
+    fn scope(&self) -> Scope {
+        Scope::new(&self.gc_system)
+    }
+
+    // 返回本次回收释放的对象数
+    #[pyo3(text_signature = "($self)")]
+    fn collect(&mut self) -> PyResult<usize> {
+        heap::collect(&self.gc_system, heap::Trigger::Manual)
+    }
+
+    // holders 只在 debug_borrows(True) 之后获取的借用才有记录
+    #[pyo3(text_signature = "($self)")]
+    fn borrow_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let borrow_state = self.gc_system.borrow_state();
+        let holders = PyList::empty(py);
+        for record in &borrow_state.holders {
+            let holder = PyDict::new(py);
+            holder.set_item("kind", if record.mutable { "mutable" } else { "shared" })?;
+            holder.set_item("location", record.location.to_string())?;
+            holder.set_item("lent", record.lent)?;
+            holder.set_item("python_stack", record.python_stack.as_deref())?;
+            holders.append(holder)?;
         }
+        let state = PyDict::new(py);
+        state.set_item("debug", arc_unsafe_refcell::debug_borrows_enabled())?;
+        state.set_item("shared", borrow_state.shared)?;
+        state.set_item("mutable", borrow_state.mutable)?;
+        state.set_item("holders", holders)?;
+        Ok(state)
     }
 
-    fn clone(&mut self) -> Self {
-        let new_gc_ref = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => {
-                match try_copy_as_vmobject(&mut self.gc_ref, &mut gc_system) {
-                    Ok(new_ref) => new_ref,
-                    Err(mut e) => {
-                        panic!("Failed to copy VMObject: {}", e.to_string());
-                    }
-                }
-            },
-            Err(_) => {
-                panic!("Failed to borrow GC system");
-            }
+    // by_type 中每个类型对应 {"count": 对象数, "bytes": 近似字节数}
+    #[pyo3(text_signature = "($self)")]
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let census = match self.gc_system.borrow() {
+            Ok(gc_system) => heap::census(&gc_system),
+            Err(e) => return Err(borrow_error("Unable to get GC statistics", e)),
         };
-        VMObject {
-            gc_ref: new_gc_ref,
-            gc_system: self.gc_system.clone(),
+        let by_type = PyDict::new(py);
+        let mut live_objects = 0;
+        let mut approx_bytes = 0;
+        for (type_name, (count, bytes)) in census {
+            let entry = PyDict::new(py);
+            entry.set_item("count", count)?;
+            entry.set_item("bytes", bytes)?;
+            by_type.set_item(type_name, entry)?;
+            live_objects += count;
+            approx_bytes += bytes;
+        }
+        let heap_stats = heap::stats(&self.gc_system);
+        let stats = PyDict::new(py);
+        stats.set_item("live_objects", live_objects)?;
+        stats.set_item("approx_bytes", approx_bytes)?;
+        stats.set_item("by_type", by_type)?;
+        stats.set_item("collections", heap_stats.collections)?;
+        stats.set_item("last_freed", heap_stats.last_freed)?;
+        stats.set_item("total_freed", heap_stats.total_freed)?;
+        stats.set_item("collect_time", heap_stats.collect_time.as_secs_f64())?;
+        let by_trigger = PyDict::new(py);
+        for trigger in heap::Trigger::ALL {
+            by_trigger.set_item(trigger.name(), heap_stats.by_trigger[trigger as usize])?;
         }
+        stats.set_item("by_trigger", by_trigger)?;
+        stats.set_item("last_trigger", heap_stats.last_trigger.map(heap::Trigger::name))?;
+        Ok(stats)
     }
-}
 
-#[pymethods]
-impl GCSystem {
-    #[new]
-    #[pyo3(text_signature = "($cls)")]
-    fn new() -> Self {
-        GCSystem {
-            gc_system: ArcUnsafeRefCellWrapper::new(XlangGCSystem::new(None)),
+    #[pyo3(text_signature = "($self)")]
+    fn object_count(&self) -> PyResult<usize> {
+        match self.gc_system.borrow() {
+            Ok(gc_system) => Ok(gc_system._count()),
+            Err(e) => Err(borrow_error("Unable to get object count", e)),
         }
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn collect(&mut self) {
-        match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.collect(),
-            Err(_) => {
-                panic!("Unable to collect garbage due to borrow error");
-            }
+    // 未传入的参数恢复为默认值，传入 None 关闭对应的触发条件
+    #[pyo3(signature = (
+        allocation_threshold=Some(heap::DEFAULT_ALLOCATION_THRESHOLD),
+        collect_after_call=false,
+        step_interval=None
+    ))]
+    fn set_policy(
+        &self,
+        allocation_threshold: Option<usize>,
+        collect_after_call: bool,
+        step_interval: Option<usize>,
+    ) -> PyResult<()> {
+        if allocation_threshold == Some(0) || step_interval == Some(0) {
+            return Err(PyValueError::new_err(
+                "allocation_threshold and step_interval must be positive or None",
+            ));
         }
+        heap::set_policy(
+            &self.gc_system,
+            heap::GcPolicy {
+                allocation_threshold,
+                collect_after_call,
+                step_interval,
+            },
+        );
+        Ok(())
     }
 
     #[pyo3(text_signature = "($self)")]
-    fn object_count(&self) -> usize {
-        match self.gc_system.borrow() {
-            Ok(gc_system) => gc_system._count(),
-            Err(_) => {
-                panic!("Unable to get object count due to borrow error");
+    fn get_policy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (policy, enabled) = heap::policy(&self.gc_system);
+        let result = PyDict::new(py);
+        result.set_item("allocation_threshold", policy.allocation_threshold)?;
+        result.set_item("collect_after_call", policy.collect_after_call)?;
+        result.set_item("step_interval", policy.step_interval)?;
+        result.set_item("enabled", enabled)?;
+        Ok(result)
+    }
+
+    // with gc.paused(): 块内不进行自动回收，显式 collect() 不受影响
+    #[pyo3(text_signature = "($self)")]
+    fn paused(&self) -> CollectionPause {
+        CollectionPause::new(&self.gc_system)
+    }
+
+    // type 可以是类型名（与 typeof 一致）或包装类，例如 "string" 或 VMString
+    #[pyo3(signature = (r#type=None))]
+    fn live_objects(
+        &self,
+        r#type: Option<&Bound<'_, PyAny>>,
+        py: Python,
+    ) -> PyResult<Vec<PyObject>> {
+        let type_name = match r#type {
+            Some(filter) if filter.is_instance_of::<PyString>() => Some(filter.extract::<String>()?),
+            _ => None,
+        };
+        let objects: Vec<XlangGCRef> = match self.gc_system.borrow() {
+            Ok(gc_system) => gc_system
+                ._get_all_objects()
+                .iter()
+                .filter(|gc_ref| {
+                    type_name
+                        .as_deref()
+                        .is_none_or(|name| heap::census_type_name(gc_ref) == name)
+                })
+                .cloned()
+                .collect(),
+            Err(e) => return Err(borrow_error("Unable to list live objects", e)),
+        };
+        let wrappers = heap::wrap_all(objects, &self.gc_system, py)?;
+        match r#type {
+            Some(filter) if type_name.is_none() => {
+                let mut matched = Vec::new();
+                for wrapper in wrappers {
+                    if wrapper.bind(py).is_instance(filter)? {
+                        matched.push(wrapper);
+                    }
+                }
+                Ok(matched)
             }
+            _ => Ok(wrappers),
+        }
+    }
+
+    // 不持有引用的句柄，对象被回收后调用句柄返回 None
+    #[pyo3(text_signature = "($self, value)")]
+    fn weak(&self, value: &Bound<'_, PyAny>) -> PyResult<WeakHandle> {
+        let mut target = extract_xlang_gc_ref_in(value, &self.gc_system)?;
+        let handle = WeakHandle::new(&self.gc_system, &target);
+        target.drop_ref();
+        Ok(handle)
+    }
+
+    // 堆中引用了 value 的对象
+    #[pyo3(text_signature = "($self, value)")]
+    fn referrers(&self, value: &Bound<'_, PyAny>, py: Python) -> PyResult<Vec<PyObject>> {
+        let mut target = extract_xlang_gc_ref_in(value, &self.gc_system)?;
+        let objects = match self.gc_system.borrow() {
+            Ok(gc_system) => heap::referrers(&gc_system, &target),
+            Err(e) => return Err(borrow_error("Unable to find referrers", e)),
+        };
+        target.drop_ref();
+        heap::wrap_all(objects, &self.gc_system, py)
+    }
+
+    // value 直接引用的对象，顺序不固定
+    #[pyo3(text_signature = "($self, value)")]
+    fn referents(&self, value: &Bound<'_, PyAny>, py: Python) -> PyResult<Vec<PyObject>> {
+        let mut target = extract_xlang_gc_ref_in(value, &self.gc_system)?;
+        let objects = heap::referents(&target);
+        target.drop_ref();
+        heap::wrap_all(objects, &self.gc_system, py)
+    }
+
+    // 把其他 GCSystem 中的值图复制到这个堆；已经属于这个堆的值原样返回
+    #[pyo3(text_signature = "($self, value)")]
+    fn adopt(&self, value: &Bound<'_, VMValue>, py: Python) -> PyResult<PyObject> {
+        let source = value.borrow();
+        if heap_core(&source.gc_system) == heap_core(&self.gc_system) {
+            source.value_ref()?;
+            return Ok(value.clone().into_any().unbind());
         }
+        let graph = PortableGraph::capture(source.value_ref()?).map_err(|e| match e {
+            PortableError::Unsupported(_) => PyTypeError::new_err(e.to_string()),
+            _ => PyValueError::new_err(e.to_string()),
+        })?;
+        let mut root = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => graph.build(&mut gc_system),
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        let adopted = xlang_gc_ref_to_py_object(&mut root, self.gc_system.clone(), py);
+        root.drop_ref();
+        adopted
+    }
+
+    #[pyo3(text_signature = "($self)")]
+    fn snapshot(&self) -> PyResult<HeapSnapshot> {
+        HeapSnapshot::take(&self.gc_system)
     }
 
     #[pyo3(text_signature = "($self, value)")]
-    fn new_int(&mut self, value: i64) -> VMInt {
+    fn new_int(&mut self, value: i64) -> PyResult<VMInt> {
         VMInt::create(self, value)
     }
 
     #[pyo3(text_signature = "($self, value)")]
-    fn new_float(&mut self, value: f64) -> VMFloat {
+    fn new_float(&mut self, value: f64) -> PyResult<VMFloat> {
         VMFloat::create(self, value)
     }
 
     #[pyo3(text_signature = "($self, value)")]
-    fn new_string(&mut self, value: String) -> VMString {
+    fn new_string(&mut self, value: String) -> PyResult<VMString> {
         VMString::create(self, value)
     }
 
     #[pyo3(text_signature = "($self)")]
-    fn new_null(&mut self) -> VMNull {
+    fn new_null(&mut self) -> PyResult<VMNull> {
         VMNull::create(self)
     }
 
     #[pyo3(text_signature = "($self, value)")]
-    fn new_bytes(&mut self, value: Vec<u8>) -> VMBytes {
-        VMBytes::create(self, value)
+    fn new_boolean(&mut self, value: bool) -> PyResult<VMBoolean> {
+        VMBoolean::create(self, value)
+    }
+
+    #[pyo3(text_signature = "($self, value)")]
+    fn new_bytes(&mut self, value: &Bound<'_, PyAny>) -> PyResult<VMBytes> {
+        VMBytes::create(self, copy_from_buffer(value)?)
     }
 
     #[pyo3(text_signature = "($self, key, value, py)")]
@@ -1683,13 +3251,49 @@ impl GCSystem {
         VMTuple::create(self, values, py)
     }
 
+    // 对象转换为字符串键的 VMKeyVal 或 VMNamed 组成的元组
+    #[allow(clippy::wrong_self_convention)]
+    #[pyo3(signature = (data, object_pairs="keyval"))]
+    fn from_json(
+        &mut self,
+        data: &Bound<'_, PyAny>,
+        object_pairs: &str,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        let object_pairs = json::ObjectPairs::parse(object_pairs)?;
+        let data = if let Ok(text) = data.downcast::<PyString>() {
+            text.to_str()?.as_bytes().to_vec()
+        } else {
+            copy_from_buffer(data)?
+        };
+        let mut root = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => json::from_json(&data, object_pairs, &mut gc_system)?,
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        let value = xlang_gc_ref_to_py_object(&mut root, self.gc_system.clone(), py);
+        root.drop_ref();
+        value
+    }
+
+    // 只接受字面量，不会编译或执行任何代码
+    #[pyo3(text_signature = "($self, text)")]
+    fn parse_literal(&mut self, text: &str, py: Python) -> PyResult<PyObject> {
+        let mut root = match self.gc_system.borrow_mut() {
+            Ok(mut gc_system) => literal::parse_literal(text, &mut gc_system)?,
+            Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+        };
+        let value = xlang_gc_ref_to_py_object(&mut root, self.gc_system.clone(), py);
+        root.drop_ref();
+        value
+    }
+
     #[pyo3(text_signature = "($self, value, py)")]
     fn new_wrapper(&mut self, value: PyObject, py: Python) -> PyResult<VMWrapper> {
         let mut xlang_ref =
             extract_xlang_gc_ref_with_gc_arc(value.bind(py), self.gc_system.clone())?;
         let wrapped = VMWrapper::create(self, &mut xlang_ref);
         xlang_ref.drop_ref(); // Drop the cloned ref
-        Ok(wrapped)
+        wrapped
     }
 
     #[pyo3(text_signature = "($self)")]
@@ -1698,7 +3302,7 @@ impl GCSystem {
     }
 
     #[pyo3(text_signature = "($self, start, end)")]
-    fn new_range(&mut self, start: i64, end: i64) -> VMRange {
+    fn new_range(&mut self, start: i64, end: i64) -> PyResult<VMRange> {
         VMRange::create(self, start, end)
     }
 
@@ -1715,17 +3319,20 @@ impl GCSystem {
         value: &Bound<'_, PyAny>,
         py: Python,
     ) -> PyResult<PyObject> {
-        if let Ok(s) = value.extract::<String>() {
-            let vm_obj = self.new_string(s);
+        if let Ok(b) = value.downcast::<PyBool>() {
+            let vm_obj = self.new_boolean(b.is_true())?;
+            Ok(Py::new(py, vm_obj)?.into())
+        } else if let Ok(s) = value.extract::<String>() {
+            let vm_obj = self.new_string(s)?;
             Ok(Py::new(py, vm_obj)?.into())
         } else if let Ok(i) = value.extract::<i64>() {
-            let vm_obj = self.new_int(i);
+            let vm_obj = self.new_int(i)?;
             Ok(Py::new(py, vm_obj)?.into())
         } else if let Ok(f) = value.extract::<f64>() {
-            let vm_obj = self.new_float(f);
+            let vm_obj = self.new_float(f)?;
             Ok(Py::new(py, vm_obj)?.into())
         } else if value.is_none() {
-            let vm_obj = self.new_null();
+            let vm_obj = self.new_null()?;
             Ok(Py::new(py, vm_obj)?.into())
         // 检查集合类型应在通用的字节提取之前
         } else if let Ok(dict) = value.downcast::<PyDict>() {
@@ -1749,12 +3356,15 @@ impl GCSystem {
         // 明确检查 PyBytes 类型
         } else if let Ok(py_bytes) = value.downcast::<pyo3::types::PyBytes>() {
             let b = py_bytes.as_bytes().to_vec(); // 从 PyBytes 获取 Vec<u8>
-            let vm_obj = self.new_bytes(b);
+            let vm_obj = VMBytes::create(self, b)?;
             Ok(Py::new(py, vm_obj)?.into())
         // （可选）如果也想处理 PyByteArray
         } else if let Ok(py_byte_array) = value.downcast::<pyo3::types::PyByteArray>() {
-            let b = py_byte_array.as_bytes().to_vec();
-            let vm_obj = self.new_bytes(b);
+            let b = py_byte_array.to_vec();
+            let vm_obj = VMBytes::create(self, b)?;
+            Ok(Py::new(py, vm_obj)?.into())
+        } else if let Some((start, end)) = python_range_bounds(value)? {
+            let vm_obj = VMRange::create(self, start, end)?;
             Ok(Py::new(py, vm_obj)?.into())
         } else if let Ok(py_set) = value.downcast::<pyo3::types::PySet>() {
             // Convert set to list
@@ -1766,18 +3376,7 @@ impl GCSystem {
             Ok(Py::new(py, vm_tuple_struct)?.into())
         }
         // Check if it's already one of our wrapped VM types
-        else if value.is_instance_of::<VMInt>()
-            || value.is_instance_of::<VMFloat>()
-            || value.is_instance_of::<VMString>()
-            || value.is_instance_of::<VMNull>()
-            || value.is_instance_of::<VMBytes>()
-            || value.is_instance_of::<VMKeyVal>()
-            || value.is_instance_of::<VMNamed>()
-            || value.is_instance_of::<VMTuple>()
-            || value.is_instance_of::<VMWrapper>()
-            || value.is_instance_of::<VMRange>()
-            || value.is_instance_of::<WrappedPyFunction>()
-            || value.is_instance_of::<VMObject>()
+        else if value.is_instance_of::<VMValue>()
         {
             Ok(value.to_object(py))
         } else {
@@ -1817,6 +3416,7 @@ impl GCSystem {
     /// Alternative name for creating an xlang key-value tuple from a Python dictionary.
     /// Functionally identical to `new_dict`.
     #[pyo3(text_signature = "($self, pydict)")]
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_pydict(&mut self, pydict: &Bound<'_, PyDict>, py: Python) -> PyResult<VMTuple> {
         unsafe { self._py_dict_to_keyval_tuple(pydict, py) }
     }
@@ -1834,11 +3434,98 @@ create_exception!(
     pyo3::exceptions::PyException
 );
 create_exception!(xlang_py, XlangExecutionError, pyo3::exceptions::PyException);
+create_exception!(xlang_py, XlangLeakError, pyo3::exceptions::PyException);
+create_exception!(xlang_py, CrossHeapError, PyValueError);
+create_exception!(xlang_py, XlangBorrowError, pyo3::exceptions::PyRuntimeError);
+
+// 堆正被其他操作借用时抛出 XlangBorrowError，而不是 panic
+pub(crate) fn borrow_error(what: &str, e: ArcUnsafeRefCellError) -> PyErr {
+    XlangBorrowError::new_err(format!("{}: {}", what, e))
+}
+
+import_exception!(pickle, PicklingError);
+import_exception!(pickle, UnpicklingError);
+
+thread_local! {
+    // pickle 等无法显式传入 GCSystem 的入口使用的堆
+    static DEFAULT_GC: RefCell<Option<ArcUnsafeRefCellWrapper<XlangGCSystem>>> = const { RefCell::new(None) };
+}
+
+fn new_gc_system() -> ArcUnsafeRefCellWrapper<XlangGCSystem> {
+    // 关闭核心库自带的回收触发，由 heap 中的策略决定何时回收
+    let mut core_gc = XlangGCSystem::new(Some((usize::MAX, usize::MAX)));
+    core_gc.check_and_collect();
+    let gc_system = ArcUnsafeRefCellWrapper::new(core_gc);
+    heap::register(&gc_system);
+    gc_system
+}
+
+fn default_gc_system() -> ArcUnsafeRefCellWrapper<XlangGCSystem> {
+    DEFAULT_GC.with(|default_gc| {
+        default_gc
+            .borrow_mut()
+            .get_or_insert_with(new_gc_system)
+            .clone()
+    })
+}
+
+// 记录每个借用的获取位置和 Python 调用栈，返回之前的设置
+#[pyfunction]
+#[pyo3(signature = (enabled=true))]
+fn debug_borrows(enabled: bool) -> bool {
+    arc_unsafe_refcell::set_debug_borrows(enabled)
+}
+
+#[pyfunction]
+fn default_gc() -> GCSystem {
+    GCSystem::from_heap(default_gc_system())
+}
+
+// 传入 None 时恢复为惰性创建的默认堆，返回之前的默认 GCSystem
+#[pyfunction]
+#[pyo3(signature = (gc=None))]
+fn set_default_gc(gc: Option<&GCSystem>) -> Option<GCSystem> {
+    let previous = DEFAULT_GC.with(|default_gc| {
+        default_gc
+            .borrow_mut()
+            .take()
+            .map(GCSystem::from_heap)
+    });
+    if let Some(gc) = gc {
+        DEFAULT_GC.with(|default_gc| *default_gc.borrow_mut() = Some(gc.gc_system.clone()));
+    }
+    previous
+}
+
+// pickle 的重建函数
+#[pyfunction]
+#[pyo3(signature = (state, gc=None))]
+fn _restore_value(state: &[u8], gc: Option<&GCSystem>, py: Python) -> PyResult<PyObject> {
+    let graph =
+        PortableGraph::from_bytes(state).map_err(|e| UnpicklingError::new_err(e.to_string()))?;
+    let gc_system = match gc {
+        Some(gc) => gc.gc_system.clone(),
+        None => default_gc_system(),
+    };
+    let mut root = match gc_system.borrow_mut() {
+        Ok(mut xlang_gc) => graph.build(&mut xlang_gc),
+        Err(e) => return Err(borrow_error("Failed to borrow GC system", e)),
+    };
+    let value = xlang_gc_ref_to_py_object(&mut root, gc_system, py);
+    root.drop_ref();
+    value
+}
 
 // 修复模块导出
 #[pymodule(name = "xlang_py")]
 fn my_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<GCSystem>()?;
+    m.add_class::<HeapSnapshot>()?;
+    m.add_class::<CollectionPause>()?;
+    m.add_class::<LeakCheck>()?;
+    m.add_class::<Scope>()?;
+    m.add_class::<WeakHandle>()?;
+    m.add_class::<VMValue>()?;
     m.add_class::<VMInt>()?;
     m.add_class::<VMFloat>()?;
     m.add_class::<VMString>()?;
@@ -1849,6 +3536,11 @@ fn my_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<VMTuple>()?;
     m.add_class::<VMWrapper>()?;
     m.add_class::<VMRange>()?;
+    m.add_class::<VMBoolean>()?;
+    m.add_class::<VMSet>()?;
+    m.add_class::<VMInstructions>()?;
+    m.add_class::<VMCLambdaInstruction>()?;
+    m.add_class::<VMObject>()?;
 
     m.add_class::<Lambda>()?;
     m.add_class::<WrappedPyFunction>()?;
@@ -1865,6 +3557,14 @@ fn my_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
         py.get_type::<XlangTranslationError>(),
     )?;
     m.add("XlangExecutionError", py.get_type::<XlangExecutionError>())?;
+    m.add("XlangLeakError", py.get_type::<XlangLeakError>())?;
+    m.add("CrossHeapError", py.get_type::<CrossHeapError>())?;
+    m.add("XlangBorrowError", py.get_type::<XlangBorrowError>())?;
+
+    m.add_function(wrap_pyfunction!(debug_borrows, m)?)?;
+    m.add_function(wrap_pyfunction!(default_gc, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default_gc, m)?)?;
+    m.add_function(wrap_pyfunction!(_restore_value, m)?)?;
 
     // 添加模块级函数和常量
     m.add("__doc__", "XLang-Rust for python")?;
@@ -0,0 +1,289 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use pyo3::exceptions::{PyReferenceError, PyRuntimeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyCFunction, PyDict, PyList, PyTuple};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+use crate::arc_unsafe_refcell::ArcUnsafeRefCellWrapper;
+use crate::heap;
+use crate::VMValue;
+
+/// Whether a wrapper still refers to a live heap object.
+///
+/// Shared by the `VMValue` base and the subclass part of one Python object, so
+/// releasing through either side turns the whole wrapper into a dead handle.
+#[derive(Clone, Default)]
+pub(crate) struct Liveness(Rc<Cell<bool>>);
+
+impl Liveness {
+    pub(crate) fn check(&self) -> PyResult<()> {
+        if self.0.get() {
+            Err(PyReferenceError::new_err("VM value has been released"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn is_released(&self) -> bool {
+        self.0.get()
+    }
+
+    pub(crate) fn release(&self) {
+        self.0.set(true);
+    }
+}
+
+/// Uncounted alias of the object owned by the `VMValue` base.
+///
+/// Every access is checked against the wrapper's liveness, because the object
+/// may already be freed once the wrapper has been released.
+#[derive(Clone)]
+pub(crate) struct AliasRef {
+    gc_ref: XlangGCRef,
+    liveness: Liveness,
+}
+
+impl AliasRef {
+    pub(crate) fn new(gc_ref: XlangGCRef) -> Self {
+        AliasRef {
+            gc_ref,
+            liveness: Liveness::default(),
+        }
+    }
+
+    pub(crate) fn get(&self) -> PyResult<&XlangGCRef> {
+        self.liveness.check()?;
+        Ok(&self.gc_ref)
+    }
+
+    pub(crate) fn get_mut(&mut self) -> PyResult<&mut XlangGCRef> {
+        self.liveness.check()?;
+        Ok(&mut self.gc_ref)
+    }
+
+    // 创建 Python 对象时调用，每个包装对象使用独立的 Liveness
+    pub(crate) fn bind(&mut self) -> (XlangGCRef, Liveness) {
+        self.liveness = Liveness::default();
+        (self.gc_ref.clone(), self.liveness.clone())
+    }
+}
+
+/// Counted references owned by one `with gc.scope():` block.
+pub(crate) struct ScopeFrame {
+    entries: RefCell<Vec<Option<(XlangGCRef, Liveness)>>>,
+    parent: Option<Rc<ScopeFrame>>,
+    closed: Cell<bool>,
+}
+
+impl ScopeFrame {
+    pub(crate) fn new(parent: Option<Rc<ScopeFrame>>) -> Self {
+        ScopeFrame {
+            entries: RefCell::new(Vec::new()),
+            parent,
+            closed: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    pub(crate) fn adopt(&self, gc_ref: XlangGCRef, liveness: Liveness) -> usize {
+        let mut entries = self.entries.borrow_mut();
+        entries.push(Some((gc_ref, liveness)));
+        entries.len() - 1
+    }
+
+    pub(crate) fn take(&self, index: usize) -> Option<(XlangGCRef, Liveness)> {
+        self.entries.borrow_mut().get_mut(index).and_then(Option::take)
+    }
+
+    // 最近的仍然打开的外层作用域
+    pub(crate) fn open_parent(&self) -> Option<Rc<ScopeFrame>> {
+        let mut parent = self.parent.clone();
+        while let Some(frame) = parent {
+            if !frame.is_closed() {
+                return Some(frame);
+            }
+            parent = frame.parent.clone();
+        }
+        None
+    }
+
+    fn close(&self) {
+        self.closed.set(true);
+        let entries = std::mem::take(&mut *self.entries.borrow_mut());
+        for (mut gc_ref, liveness) in entries.into_iter().flatten() {
+            gc_ref.drop_ref();
+            liveness.release();
+        }
+    }
+}
+
+/// Who releases the counted reference of a `VMValue`.
+pub(crate) enum Owner {
+    Wrapper,
+    Scope(Rc<ScopeFrame>, usize),
+}
+
+impl Owner {
+    // 新包装对象的引用交给当前作用域，没有作用域时由包装对象自己持有
+    pub(crate) fn adopt(
+        gc_ref: &Option<XlangGCRef>,
+        liveness: &Liveness,
+        gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    ) -> Owner {
+        match (gc_ref, heap::current_scope(gc_system)) {
+            (Some(gc_ref), Some(frame)) => {
+                let index = frame.adopt(gc_ref.clone(), liveness.clone());
+                Owner::Scope(frame, index)
+            }
+            _ => Owner::Wrapper,
+        }
+    }
+
+    /// Drops the counted reference, wherever it is held.
+    pub(crate) fn release(self, gc_ref: Option<XlangGCRef>) {
+        match self {
+            Owner::Wrapper => {
+                if let Some(mut gc_ref) = gc_ref {
+                    gc_ref.drop_ref();
+                }
+            }
+            Owner::Scope(frame, index) => {
+                if let Some((mut gc_ref, _)) = frame.take(index) {
+                    gc_ref.drop_ref();
+                }
+            }
+        }
+    }
+}
+
+/// Context manager returned by `GCSystem.scope()`.
+///
+/// Wrappers created inside the block are released when it exits unless they
+/// are passed to `keep()`. Used as a decorator, each call runs in a new scope
+/// and the returned values are kept.
+#[pyclass(unsendable)]
+pub struct Scope {
+    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    frame: Option<Rc<ScopeFrame>>,
+}
+
+impl Scope {
+    pub(crate) fn new(gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>) -> Self {
+        Scope {
+            gc_system: gc_system.clone(),
+            frame: None,
+        }
+    }
+
+    fn enter(&mut self) -> PyResult<()> {
+        if self.frame.is_some() {
+            return Err(PyRuntimeError::new_err("scope is already active"));
+        }
+        let frame = Rc::new(ScopeFrame::new(heap::current_scope(&self.gc_system)));
+        heap::push_scope(&self.gc_system, frame.clone());
+        self.frame = Some(frame);
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            heap::remove_scope(&self.gc_system, &frame);
+            frame.close();
+        }
+    }
+
+    fn keep_value(&self, value: &Bound<'_, VMValue>) -> PyResult<()> {
+        let frame = match &self.frame {
+            Some(frame) => frame,
+            None => return Err(PyRuntimeError::new_err("scope is not active")),
+        };
+        value.borrow_mut().move_out_of(frame)
+    }
+
+    // 返回值本身以及（嵌套）包含在 tuple/list 中的 VM 值都会保留
+    fn keep_returned(
+        &self,
+        result: &Bound<'_, PyAny>,
+        visited: &mut HashSet<usize>,
+    ) -> PyResult<()> {
+        if let Ok(value) = result.downcast::<VMValue>() {
+            return self.keep_value(value);
+        }
+        if !visited.insert(result.as_ptr() as usize) {
+            return Ok(());
+        }
+        let items: Vec<Bound<'_, PyAny>> = if let Ok(tuple) = result.downcast::<PyTuple>() {
+            tuple.iter().collect()
+        } else if let Ok(list) = result.downcast::<PyList>() {
+            list.iter().collect()
+        } else {
+            Vec::new()
+        };
+        for item in items {
+            self.keep_returned(&item, visited)?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Scope {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        slf.enter()?;
+        Ok(slf)
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.exit();
+        false
+    }
+
+    // 移交给外层作用域，没有外层作用域时由包装对象自己持有
+    #[pyo3(text_signature = "($self, value)")]
+    fn keep<'py>(&self, value: &Bound<'py, VMValue>) -> PyResult<Bound<'py, VMValue>> {
+        self.keep_value(value)?;
+        Ok(value.clone())
+    }
+
+    fn __call__(&self, func: PyObject, py: Python<'_>) -> PyResult<Py<PyCFunction>> {
+        let gc = Py::new(
+            py,
+            crate::GCSystem::from_heap(self.gc_system.clone()),
+        )?;
+        let scoped = PyCFunction::new_closure(
+            py,
+            None,
+            None,
+            move |args: &Bound<'_, PyTuple>,
+                  kwargs: Option<&Bound<'_, PyDict>>|
+                  -> PyResult<PyObject> {
+                let py = args.py();
+                let mut scope = Scope::new(&gc.borrow(py).gc_system);
+                scope.enter()?;
+                let result = func
+                    .call(py, args, kwargs)
+                    .and_then(|result| scope.keep_returned(result.bind(py), &mut HashSet::new()).map(|_| result));
+                scope.exit();
+                result
+            },
+        )?;
+        Ok(scoped.unbind())
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
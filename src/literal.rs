@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use xlang_frontend::parser::ast::{
+    ast_token_stream, build_ast, ASTNode, ASTNodeModifier, ASTNodeOperation, ASTNodeType,
+};
+use xlang_frontend::parser::lexer::lexer;
+use xlang_vm_core::executor::variable::{
+    try_alias_as_vmobject, try_const_alias_as_vmobject, VMBoolean as XlangVMBoolean,
+    VMBytes as XlangVMBytes, VMFloat as XlangVMFloat, VMInt as XlangVMInt,
+    VMKeyVal as XlangVMKeyVal, VMNamed as XlangVMNamed, VMNull as XlangVMNull,
+    VMRange as XlangVMRange, VMString as XlangVMString, VMTuple as XlangVMTuple,
+    VMWrapper as XlangVMWrapper,
+};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+use crate::{new_tuple_from_refs, release_all, vm_type_name, XlangCompilationError};
+
+/// Renders the value graph rooted at `gc_ref` as xlang literal syntax.
+/// Compound values nested inside other operators are parenthesized so the
+/// output parses back to the same shape.
+pub(crate) fn to_source(gc_ref: &XlangGCRef) -> PyResult<String> {
+    let mut output = String::new();
+    write_value(gc_ref, &mut output, &mut HashSet::new())?;
+    Ok(output)
+}
+
+fn write_value(
+    gc_ref: &XlangGCRef,
+    output: &mut String,
+    visiting: &mut HashSet<XlangGCRef>,
+) -> PyResult<()> {
+    if !visiting.insert(gc_ref.clone()) {
+        return Err(PyValueError::new_err(
+            "cyclic VM value graphs cannot be written as literals",
+        ));
+    }
+    // 别名按应用顺序保存，最外层的写在最前面
+    let aliases = match try_const_alias_as_vmobject(&mut gc_ref.clone()) {
+        Ok(alias) => alias.clone(),
+        Err(mut e) => {
+            e.consume_ref();
+            Vec::new()
+        }
+    };
+    for alias in aliases.iter().rev() {
+        if !is_identifier(alias) {
+            return Err(PyValueError::new_err(format!(
+                "alias '{}' cannot be written as a literal",
+                alias
+            )));
+        }
+        output.push_str(alias);
+        output.push_str("::");
+    }
+    if aliases.is_empty() || is_bare_atom(gc_ref) {
+        write_bare(gc_ref, output, visiting)?;
+    } else {
+        output.push('(');
+        write_bare(gc_ref, output, visiting)?;
+        output.push(')');
+    }
+    visiting.remove(gc_ref);
+    Ok(())
+}
+
+fn write_bare(
+    gc_ref: &XlangGCRef,
+    output: &mut String,
+    visiting: &mut HashSet<XlangGCRef>,
+) -> PyResult<()> {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        output.push_str(&gc_ref.as_const_type::<XlangVMInt>().value.to_string());
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        let value = gc_ref.as_const_type::<XlangVMFloat>().value;
+        if !value.is_finite() {
+            return Err(PyValueError::new_err(format!(
+                "float value {} has no literal form",
+                value
+            )));
+        }
+        // Debug 格式总是带小数点或指数，读回时仍是浮点数
+        output.push_str(&format!("{:?}", value));
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        write_string(&gc_ref.as_const_type::<XlangVMString>().value, output);
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        let value = gc_ref.as_const_type::<XlangVMBoolean>().value;
+        output.push_str(if value { "true" } else { "false" });
+    } else if gc_ref.isinstance::<XlangVMNull>() {
+        output.push_str("null");
+    } else if gc_ref.isinstance::<XlangVMBytes>() {
+        output.push_str("$\"");
+        output.push_str(&BASE64.encode(&gc_ref.as_const_type::<XlangVMBytes>().value));
+        output.push('"');
+    } else if gc_ref.isinstance::<XlangVMRange>() {
+        let range = gc_ref.as_const_type::<XlangVMRange>();
+        write_int_operand(range.start, output);
+        output.push_str("..");
+        write_int_operand(range.end, output);
+    } else if gc_ref.isinstance::<XlangVMKeyVal>() {
+        let keyval = gc_ref.as_const_type::<XlangVMKeyVal>();
+        write_operand(&keyval.key, output, visiting)?;
+        output.push_str(" : ");
+        write_operand(&keyval.value, output, visiting)?;
+    } else if gc_ref.isinstance::<XlangVMNamed>() {
+        let named = gc_ref.as_const_type::<XlangVMNamed>();
+        write_operand(&named.key, output, visiting)?;
+        output.push_str(" => ");
+        write_operand(&named.value, output, visiting)?;
+    } else if gc_ref.isinstance::<XlangVMWrapper>() {
+        output.push_str("wrap ");
+        write_operand(
+            &gc_ref.as_const_type::<XlangVMWrapper>().value_ref,
+            output,
+            visiting,
+        )?;
+    } else if gc_ref.isinstance::<XlangVMTuple>() {
+        let values = &gc_ref.as_const_type::<XlangVMTuple>().values;
+        output.push('(');
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                output.push_str(", ");
+            }
+            write_value(value, output, visiting)?;
+        }
+        // 单元素元组需要结尾的逗号
+        if values.len() == 1 {
+            output.push(',');
+        }
+        output.push(')');
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "VM value of type '{}' has no literal form",
+            vm_type_name(gc_ref)
+        )));
+    }
+    Ok(())
+}
+
+// 作为运算符的操作数时，非原子的值需要加括号
+fn write_operand(
+    gc_ref: &XlangGCRef,
+    output: &mut String,
+    visiting: &mut HashSet<XlangGCRef>,
+) -> PyResult<()> {
+    let mut value = String::new();
+    write_value(gc_ref, &mut value, visiting)?;
+    if is_atom(gc_ref) {
+        output.push_str(&value);
+    } else {
+        output.push('(');
+        output.push_str(&value);
+        output.push(')');
+    }
+    Ok(())
+}
+
+fn write_int_operand(value: i64, output: &mut String) {
+    if value < 0 {
+        output.push_str(&format!("({})", value));
+    } else {
+        output.push_str(&value.to_string());
+    }
+}
+
+fn is_atom(gc_ref: &XlangGCRef) -> bool {
+    let has_alias = match try_const_alias_as_vmobject(&mut gc_ref.clone()) {
+        Ok(alias) => !alias.is_empty(),
+        Err(mut e) => {
+            e.consume_ref();
+            false
+        }
+    };
+    !has_alias && is_bare_atom(gc_ref)
+}
+
+fn is_bare_atom(gc_ref: &XlangGCRef) -> bool {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        gc_ref.as_const_type::<XlangVMInt>().value >= 0
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        gc_ref
+            .as_const_type::<XlangVMFloat>()
+            .value
+            .is_sign_positive()
+    } else {
+        gc_ref.isinstance::<XlangVMString>()
+            || gc_ref.isinstance::<XlangVMBoolean>()
+            || gc_ref.isinstance::<XlangVMNull>()
+            || gc_ref.isinstance::<XlangVMBytes>()
+            || gc_ref.isinstance::<XlangVMTuple>()
+    }
+}
+
+// 词法分析器只识别 \n \t \" \\ 和 \uXXXX
+fn write_string(value: &str, output: &mut String) {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() && (c as u32) <= 0xFFFF => {
+                output.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Parses `text` with the xlang parser and builds the value it describes.
+/// Only literal syntax is accepted; nothing is compiled or executed.
+pub(crate) fn parse_literal(text: &str, gc_system: &mut XlangGCSystem) -> PyResult<XlangGCRef> {
+    let tokens = lexer::tokenize(text);
+    let tokens = lexer::reject_comment(&tokens);
+    let gathered = ast_token_stream::from_stream(&tokens);
+    let ast = build_ast(gathered).map_err(|e| {
+        XlangCompilationError::new_err(e.format(&tokens, text.to_string()).to_string())
+    })?;
+    build(&ast, gc_system)
+}
+
+fn build(node: &ASTNode, gc_system: &mut XlangGCSystem) -> PyResult<XlangGCRef> {
+    match &node.node_type {
+        ASTNodeType::Null => Ok(gc_system.new_object(XlangVMNull::new())),
+        ASTNodeType::Boolean(value) => {
+            Ok(gc_system.new_object(XlangVMBoolean::new(value == "true")))
+        }
+        ASTNodeType::Number(number) => new_number(number, false, gc_system),
+        ASTNodeType::String(value) => Ok(gc_system.new_object(XlangVMString::new(value))),
+        ASTNodeType::Base64(encoded) => {
+            let value = BASE64
+                .decode(encoded)
+                .map_err(|e| PyValueError::new_err(format!("invalid bytes literal: {}", e)))?;
+            Ok(gc_system.new_object(XlangVMBytes::new(&value)))
+        }
+        ASTNodeType::Operation(ASTNodeOperation::Subtract) if node.children.len() == 1 => {
+            match &node.children[0].node_type {
+                ASTNodeType::Number(number) => new_number(number, true, gc_system),
+                _ => Err(not_a_literal(node)),
+            }
+        }
+        ASTNodeType::Tuple => {
+            let mut items = Vec::with_capacity(node.children.len());
+            for child in &node.children {
+                if child.node_type == ASTNodeType::None {
+                    continue;
+                }
+                match build(child, gc_system) {
+                    Ok(item) => items.push(item),
+                    Err(e) => return Err(release_all(items, e)),
+                }
+            }
+            Ok(new_tuple_from_refs(items, gc_system))
+        }
+        ASTNodeType::KeyValue | ASTNodeType::NamedTo | ASTNodeType::Range => {
+            let mut left = build(&node.children[0], gc_system)?;
+            let mut right = match build(&node.children[1], gc_system) {
+                Ok(right) => right,
+                Err(e) => return Err(release_all(vec![left], e)),
+            };
+            let result = match node.node_type {
+                ASTNodeType::KeyValue => {
+                    Ok(gc_system.new_object(XlangVMKeyVal::new(&mut left, &mut right)))
+                }
+                ASTNodeType::NamedTo => {
+                    Ok(gc_system.new_object(XlangVMNamed::new(&mut left, &mut right)))
+                }
+                _ => match (range_bound(&left), range_bound(&right)) {
+                    (Some(start), Some(end)) => {
+                        Ok(gc_system.new_object(XlangVMRange::new(start, end)))
+                    }
+                    _ => Err(PyTypeError::new_err("range bounds must be integers")),
+                },
+            };
+            left.drop_ref();
+            right.drop_ref();
+            result
+        }
+        ASTNodeType::Modifier(ASTNodeModifier::Wrap) => {
+            let mut value = build(&node.children[0], gc_system)?;
+            let wrapper = gc_system.new_object(XlangVMWrapper::new(&mut value));
+            value.drop_ref();
+            Ok(wrapper)
+        }
+        ASTNodeType::Alias(alias) => {
+            let mut value = build(&node.children[0], gc_system)?;
+            match try_alias_as_vmobject(&mut value) {
+                Ok(aliases) => aliases.push(alias.clone()),
+                Err(e) => {
+                    value.drop_ref();
+                    return Err(crate::operators::vm_variable_error_to_pyerr(e));
+                }
+            }
+            Ok(value)
+        }
+        _ => Err(not_a_literal(node)),
+    }
+}
+
+// 与 IR 生成器一致：能解析为整数的就是整数，否则为浮点数
+fn new_number(number: &str, negative: bool, gc_system: &mut XlangGCSystem) -> PyResult<XlangGCRef> {
+    let signed = if negative {
+        format!("-{}", number)
+    } else {
+        number.to_string()
+    };
+    if let Ok(value) = signed.parse::<i64>() {
+        Ok(gc_system.new_object(XlangVMInt::new(value)))
+    } else if let Ok(value) = signed.parse::<f64>() {
+        Ok(gc_system.new_object(XlangVMFloat::new(value)))
+    } else {
+        Err(PyValueError::new_err(format!(
+            "invalid number literal '{}'",
+            number
+        )))
+    }
+}
+
+fn range_bound(gc_ref: &XlangGCRef) -> Option<i64> {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        Some(gc_ref.as_const_type::<XlangVMInt>().value)
+    } else {
+        None
+    }
+}
+
+fn not_a_literal(node: &ASTNode) -> PyErr {
+    PyValueError::new_err(format!("expression is not a literal: {:?}", node.node_type))
+}
@@ -0,0 +1,477 @@
+use crate::{
+    extract_xlang_gc_ref, extract_xlang_gc_ref_with_gc_arc, xlang_gc_ref_to_py_object,
+    ArcUnsafeRefCellWrapper, CrossHeapError, XlangExecutionError,
+};
+use pyo3::exceptions::{
+    PyIndexError, PyKeyError, PyOverflowError, PyTypeError, PyValueError, PyZeroDivisionError,
+};
+use pyo3::prelude::*;
+use xlang_vm_core::executor::variable::{
+    try_add_as_vmobject, try_contains_as_vmobject, try_eq_as_vmobject, try_mod_as_vmobject,
+    try_mul_as_vmobject, try_power_as_vmobject, try_repr_vmobject, try_shift_left_as_vmobject,
+    try_shift_right_as_vmobject, try_sub_as_vmobject, VMBoolean as XlangVMBoolean, VMBytes as XlangVMBytes, VMFloat as XlangVMFloat,
+    VMInt as XlangVMInt, VMNull as XlangVMNull, VMRange as XlangVMRange, VMString as XlangVMString,
+    VMTuple as XlangVMTuple, VMVariableError,
+};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+pub(crate) type BinaryOp = fn(
+    &mut XlangGCRef,
+    &mut XlangGCRef,
+    &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError>;
+
+pub(crate) type UnaryOp =
+    fn(&mut XlangGCRef, &mut XlangGCSystem) -> Result<XlangGCRef, VMVariableError>;
+
+pub(crate) type CompareOp =
+    fn(&mut XlangGCRef, &mut XlangGCRef) -> Result<bool, VMVariableError>;
+
+// 将虚拟机错误转换为对应的 Python 异常，并释放错误中持有的引用
+pub(crate) fn vm_variable_error_to_pyerr(mut e: VMVariableError) -> PyErr {
+    let message = e.to_string();
+    // to_string 以 "ValueError: " 等虚拟机的错误名开头，映射到 Python 异常类型后不再重复
+    let detail = match message.split_once(": ") {
+        Some((_, detail)) => detail.to_string(),
+        None => message.clone(),
+    };
+    let err = match e {
+        VMVariableError::TypeError(..) | VMVariableError::ValueError2Param(..) => {
+            PyTypeError::new_err(detail)
+        }
+        VMVariableError::ValueError(..) => PyValueError::new_err(detail),
+        VMVariableError::KeyNotFound(..) => PyKeyError::new_err(detail),
+        VMVariableError::IndexNotFound(..) => PyIndexError::new_err(detail),
+        VMVariableError::OverflowError(..) => PyOverflowError::new_err(detail),
+        _ => XlangExecutionError::new_err(message),
+    };
+    e.consume_ref();
+    err
+}
+
+fn is_zero_divisor(value: &XlangGCRef, other: &XlangGCRef) -> bool {
+    value.isinstance::<XlangVMInt>()
+        && other.isinstance::<XlangVMInt>()
+        && other.as_const_type::<XlangVMInt>().value == 0
+}
+
+/// Applies an xlang binary operator to `gc_ref` and a Python operand.
+/// Returns `NotImplemented` when the operand cannot be converted to a VM value,
+/// so Python can fall back to the reflected operation.
+pub(crate) fn binary_op(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    op: BinaryOp,
+    reflected: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    apply_binary_op(gc_ref, gc_system, other, op, reflected, false, py)
+}
+
+/// `%` through `try_mod_as_vmobject`, raising `ZeroDivisionError` or `OverflowError` where the VM would panic.
+pub(crate) fn modulo(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    reflected: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    apply_binary_op(gc_ref, gc_system, other, checked_mod_as_vmobject, reflected, true, py)
+}
+
+/// `**` through the checked power operator. The VM has no modular power, so a
+/// three-argument `pow()` raises `TypeError` instead of ignoring the modulus.
+pub(crate) fn power(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    modulo: &Bound<'_, PyAny>,
+    reflected: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    if !modulo.is_none() {
+        return Err(PyTypeError::new_err(
+            "pow() with a modulus is not supported for VM values",
+        ));
+    }
+    apply_binary_op(gc_ref, gc_system, other, checked_power_as_vmobject, reflected, false, py)
+}
+
+// 比较只读取两个值，不会留下跨堆引用，因此也接受其他堆的值
+fn extract_operand(
+    other: &Bound<'_, PyAny>,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+) -> PyResult<XlangGCRef> {
+    match extract_xlang_gc_ref(other) {
+        Ok(other_ref) => Ok(other_ref),
+        Err(_) => extract_xlang_gc_ref_with_gc_arc(other, gc_system.clone()),
+    }
+}
+
+fn apply_binary_op(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    op: BinaryOp,
+    reflected: bool,
+    check_zero_divisor: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    let mut other_ref = match extract_xlang_gc_ref_with_gc_arc(other, gc_system.clone()) {
+        Ok(r) => r,
+        // 结果可能引用操作数，不能混用其他堆的值
+        Err(e) if e.is_instance_of::<CrossHeapError>(py) => return Err(e),
+        Err(_) => return Ok(py.NotImplemented()),
+    };
+    let mut self_ref = gc_ref.clone();
+    let (left, right) = if reflected {
+        (&mut other_ref, &mut self_ref)
+    } else {
+        (&mut self_ref, &mut other_ref)
+    };
+    // xlang 的整数取模在除数为零时会直接 panic
+    if check_zero_divisor && is_zero_divisor(left, right) {
+        other_ref.drop_ref();
+        return Err(PyZeroDivisionError::new_err("integer modulo by zero"));
+    }
+    let result = match gc_system.borrow_mut() {
+        Ok(mut gc) => op(left, right, &mut gc),
+        Err(e) => {
+            other_ref.drop_ref();
+            return Err(XlangExecutionError::new_err(format!(
+                "Failed to borrow GC system for operator: {}",
+                e
+            )));
+        }
+    };
+    other_ref.drop_ref();
+    match result {
+        Ok(mut result_ref) => {
+            let py_object = xlang_gc_ref_to_py_object(&mut result_ref, gc_system.clone(), py);
+            result_ref.drop_ref();
+            py_object
+        }
+        Err(e) => Err(vm_variable_error_to_pyerr(e)),
+    }
+}
+
+/// Applies an xlang comparison to `gc_ref` and a Python operand.
+/// `negate` mirrors how the VM derives `<=` and `>=` from `>` and `<`.
+pub(crate) fn compare_op(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    op: CompareOp,
+    negate: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    let mut other_ref = match extract_operand(other, gc_system) {
+        Ok(r) => r,
+        Err(_) => return Ok(py.NotImplemented()),
+    };
+    let mut self_ref = gc_ref.clone();
+    let result = op(&mut self_ref, &mut other_ref);
+    other_ref.drop_ref();
+    match result {
+        Ok(value) => Ok((value != negate).into_pyobject(py)?.to_owned().into_any().unbind()),
+        Err(e) => Err(vm_variable_error_to_pyerr(e)),
+    }
+}
+
+/// Value equality with the VM's `==` operator.
+/// Returns `NotImplemented` when the operand cannot be converted to a VM value.
+pub(crate) fn equals(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    other: &Bound<'_, PyAny>,
+    negate: bool,
+    py: Python,
+) -> PyResult<PyObject> {
+    let mut other_ref = match extract_operand(other, gc_system) {
+        Ok(r) => r,
+        Err(_) => return Ok(py.NotImplemented()),
+    };
+    let result = try_eq_as_vmobject(gc_ref, &other_ref);
+    other_ref.drop_ref();
+    Ok((result != negate).into_pyobject(py)?.to_owned().into_any().unbind())
+}
+
+/// Hash of a VM value, consistent with `equals`: values that compare equal to a
+/// Python primitive hash like that primitive (so `VMInt(1)`, `1` and `1.0` collide).
+pub(crate) fn hash_value(gc_ref: &XlangGCRef, py: Python) -> PyResult<isize> {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        gc_ref.as_const_type::<XlangVMInt>().value.into_pyobject(py)?.hash()
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        gc_ref.as_const_type::<XlangVMFloat>().value.into_pyobject(py)?.hash()
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        gc_ref.as_const_type::<XlangVMString>().value.as_str().into_pyobject(py)?.hash()
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        let value = gc_ref.as_const_type::<XlangVMBoolean>().value;
+        value.into_pyobject(py)?.hash()
+    } else if gc_ref.isinstance::<XlangVMNull>() {
+        py.None().bind(py).hash()
+    } else if gc_ref.isinstance::<XlangVMRange>() {
+        // 与相等的 Python range 哈希一致
+        let range = gc_ref.as_const_type::<XlangVMRange>();
+        py.import("builtins")?.getattr("range")?.call1((range.start, range.end))?.hash()
+    } else {
+        Err(PyTypeError::new_err("unhashable VM value"))
+    }
+}
+
+/// Applies an xlang unary operator to `gc_ref`.
+pub(crate) fn unary_op(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    op: UnaryOp,
+    py: Python,
+) -> PyResult<PyObject> {
+    let mut self_ref = gc_ref.clone();
+    let result = match gc_system.borrow_mut() {
+        Ok(mut gc) => op(&mut self_ref, &mut gc),
+        Err(e) => {
+            return Err(XlangExecutionError::new_err(format!(
+                "Failed to borrow GC system for operator: {}",
+                e
+            )))
+        }
+    };
+    match result {
+        Ok(mut result_ref) => {
+            let py_object = xlang_gc_ref_to_py_object(&mut result_ref, gc_system.clone(), py);
+            result_ref.drop_ref();
+            py_object
+        }
+        // OverflowError 总是带两个操作数，一元运算只显示一次
+        Err(VMVariableError::OverflowError(mut value, mut other, message)) => {
+            let repr = try_repr_vmobject(&mut value, None).unwrap_or(format!("{:?}", value));
+            value.drop_ref();
+            other.drop_ref();
+            Err(PyOverflowError::new_err(format!("{}: {}", message, repr)))
+        }
+        Err(e) => Err(vm_variable_error_to_pyerr(e)),
+    }
+}
+
+// 与虚拟机的 unary_minus 指令保持一致
+pub(crate) fn try_negate_as_vmobject(
+    value: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    if value.isinstance::<XlangVMInt>() {
+        match value.as_const_type::<XlangVMInt>().value.checked_neg() {
+            Some(negated) => Ok(gc_system.new_object(XlangVMInt::new(negated))),
+            None => Err(VMVariableError::OverflowError(
+                value.clone_ref(),
+                value.clone_ref(),
+                "Overflow when negating".to_string(),
+            )),
+        }
+    } else if value.isinstance::<XlangVMFloat>() {
+        let negated = -value.as_const_type::<XlangVMFloat>().value;
+        Ok(gc_system.new_object(XlangVMFloat::new(negated)))
+    } else {
+        Err(VMVariableError::TypeError(
+            value.clone_ref(),
+            "Unary minus operation not supported".to_string(),
+        ))
+    }
+}
+
+// 虚拟机的整数运算溢出时会 panic（release 构建下则静默回绕），因此两侧都是整数时先用 checked_* 计算
+fn checked_int_op(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+    checked: fn(i64, i64) -> Option<i64>,
+    message: &str,
+    fallback: BinaryOp,
+) -> Result<XlangGCRef, VMVariableError> {
+    if !(left.isinstance::<XlangVMInt>() && right.isinstance::<XlangVMInt>()) {
+        return fallback(left, right, gc_system);
+    }
+    let lhs = left.as_const_type::<XlangVMInt>().value;
+    let rhs = right.as_const_type::<XlangVMInt>().value;
+    match checked(lhs, rhs) {
+        Some(result) => Ok(gc_system.new_object(XlangVMInt::new(result))),
+        None => Err(VMVariableError::OverflowError(
+            left.clone_ref(),
+            right.clone_ref(),
+            message.to_string(),
+        )),
+    }
+}
+
+fn negative_int_operand(
+    left: &XlangGCRef,
+    right: &mut XlangGCRef,
+    message: &str,
+) -> Option<VMVariableError> {
+    if left.isinstance::<XlangVMInt>()
+        && right.isinstance::<XlangVMInt>()
+        && right.as_const_type::<XlangVMInt>().value < 0
+    {
+        Some(VMVariableError::ValueError(right.clone_ref(), message.to_string()))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn checked_add_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    checked_int_op(left, right, gc_system, i64::checked_add, "Overflow when adding", try_add_as_vmobject)
+}
+
+pub(crate) fn checked_sub_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    checked_int_op(left, right, gc_system, i64::checked_sub, "Overflow when subtracting", try_sub_as_vmobject)
+}
+
+pub(crate) fn checked_mul_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    checked_int_op(left, right, gc_system, i64::checked_mul, "Overflow when multiplying", try_mul_as_vmobject)
+}
+
+// 除数为零已在 apply_binary_op 中检查，这里只剩 i64::MIN % -1
+fn checked_mod_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    checked_int_op(left, right, gc_system, i64::checked_rem, "Overflow when mod", try_mod_as_vmobject)
+}
+
+// 虚拟机把负指数强转为 u32，会得到错误的结果或误报溢出
+fn checked_power_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    if let Some(e) = negative_int_operand(left, right, "Negative exponent is not supported for integers") {
+        return Err(e);
+    }
+    checked_int_op(
+        left,
+        right,
+        gc_system,
+        |base, exponent| base.checked_pow(u32::try_from(exponent).ok()?),
+        "Overflow when power",
+        try_power_as_vmobject,
+    )
+}
+
+pub(crate) fn checked_shift_left_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    if let Some(e) = negative_int_operand(left, right, "Negative shift count") {
+        return Err(e);
+    }
+    checked_int_op(
+        left,
+        right,
+        gc_system,
+        |value, count| {
+            if value == 0 {
+                return Some(0);
+            }
+            // 移出的位必须能原样移回，否则结果已经溢出
+            let shifted = value.checked_shl(u32::try_from(count).ok()?)?;
+            (shifted >> count == value).then_some(shifted)
+        },
+        "Overflow when shifting left",
+        try_shift_left_as_vmobject,
+    )
+}
+
+pub(crate) fn checked_shift_right_as_vmobject(
+    left: &mut XlangGCRef,
+    right: &mut XlangGCRef,
+    gc_system: &mut XlangGCSystem,
+) -> Result<XlangGCRef, VMVariableError> {
+    if let Some(e) = negative_int_operand(left, right, "Negative shift count") {
+        return Err(e);
+    }
+    // 与 Python 一致，移位数超出位宽时结果为 0 或 -1
+    checked_int_op(
+        left,
+        right,
+        gc_system,
+        |value, count| Some(value >> count.min(63)),
+        "Overflow when shifting right",
+        try_shift_right_as_vmobject,
+    )
+}
+
+/// Tests membership with the VM's `in` operator.
+pub(crate) fn contains(
+    gc_ref: &XlangGCRef,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    item: &Bound<'_, PyAny>,
+) -> PyResult<bool> {
+    let mut item_ref = extract_operand(item, gc_system)?;
+    let mut self_ref = gc_ref.clone();
+    let result = try_contains_as_vmobject(&mut self_ref, &mut item_ref);
+    item_ref.drop_ref();
+    result.map_err(vm_variable_error_to_pyerr)
+}
+
+/// Truthiness of a VM value, following the `to_bool` conversions of the VM types.
+pub(crate) fn truthiness(gc_ref: &XlangGCRef) -> bool {
+    if gc_ref.isinstance::<XlangVMBoolean>() {
+        gc_ref.as_const_type::<XlangVMBoolean>().value
+    } else if gc_ref.isinstance::<XlangVMInt>() {
+        gc_ref.as_const_type::<XlangVMInt>().value != 0
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        gc_ref.as_const_type::<XlangVMFloat>().value != 0.0
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        !gc_ref.as_const_type::<XlangVMString>().value.is_empty()
+    } else if gc_ref.isinstance::<XlangVMBytes>() {
+        !gc_ref.as_const_type::<XlangVMBytes>().value.is_empty()
+    } else if gc_ref.isinstance::<XlangVMTuple>() {
+        !gc_ref.as_const_type::<XlangVMTuple>().values.is_empty()
+    } else {
+        !gc_ref.isinstance::<XlangVMNull>()
+    }
+}
+
+/// Integer conversion following the `to_int` conversions of the VM types.
+pub(crate) fn to_int(gc_ref: &XlangGCRef) -> PyResult<i64> {
+    if gc_ref.isinstance::<XlangVMInt>() {
+        Ok(gc_ref.as_const_type::<XlangVMInt>().value)
+    } else if gc_ref.isinstance::<XlangVMFloat>() {
+        Ok(gc_ref.as_const_type::<XlangVMFloat>().value as i64)
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        Ok(gc_ref.as_const_type::<XlangVMBoolean>().value as i64)
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        gc_ref.clone().as_type::<XlangVMString>().to_int().map_err(vm_variable_error_to_pyerr)
+    } else {
+        Err(PyTypeError::new_err("Cannot convert this VM value to int"))
+    }
+}
+
+/// Float conversion following the `to_float` conversions of the VM types.
+pub(crate) fn to_float(gc_ref: &XlangGCRef) -> PyResult<f64> {
+    if gc_ref.isinstance::<XlangVMFloat>() {
+        Ok(gc_ref.as_const_type::<XlangVMFloat>().value)
+    } else if gc_ref.isinstance::<XlangVMInt>() {
+        Ok(gc_ref.as_const_type::<XlangVMInt>().value as f64)
+    } else if gc_ref.isinstance::<XlangVMBoolean>() {
+        Ok(gc_ref.as_const_type::<XlangVMBoolean>().value as i64 as f64)
+    } else if gc_ref.isinstance::<XlangVMString>() {
+        gc_ref.clone().as_type::<XlangVMString>().to_float().map_err(vm_variable_error_to_pyerr)
+    } else {
+        Err(PyTypeError::new_err("Cannot convert this VM value to float"))
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use xlang_vm_core::executor::variable::{
+    try_alias_as_vmobject, try_const_alias_as_vmobject, VMBoolean as XlangVMBoolean,
+    VMBytes as XlangVMBytes, VMFloat as XlangVMFloat, VMInt as XlangVMInt,
+    VMKeyVal as XlangVMKeyVal, VMNamed as XlangVMNamed, VMNull as XlangVMNull,
+    VMRange as XlangVMRange, VMString as XlangVMString, VMTuple as XlangVMTuple,
+    VMWrapper as XlangVMWrapper,
+};
+use xlang_vm_core::gc::{GCRef as XlangGCRef, GCSystem as XlangGCSystem};
+
+use crate::{new_tuple_from_refs, vm_type_name};
+
+// 格式变化时递增，旧数据在反序列化时会被拒绝
+const PORTABLE_VERSION: u32 = 1;
+
+// 与堆无关的值，子节点用它们在 nodes 中的下标引用
+#[derive(Serialize, Deserialize)]
+enum PortableKind {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Bytes(Vec<u8>),
+    Range(i64, i64),
+    KeyVal(usize, usize),
+    Named(usize, usize),
+    Tuple(Vec<usize>),
+    Wrapper(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortableNode {
+    kind: PortableKind,
+    alias: Vec<String>,
+}
+
+/// A heap-independent snapshot of a VM value graph.
+///
+/// Nodes are stored children-first, so every reference points to an earlier
+/// node and the root is the last one. Shared sub-values are stored once and
+/// stay shared after rebuilding.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PortableGraph {
+    version: u32,
+    nodes: Vec<PortableNode>,
+}
+
+pub(crate) enum PortableError {
+    // 无法脱离堆存在的值，例如 lambda 和原生函数
+    Unsupported(&'static str),
+    Cyclic,
+    Malformed(String),
+}
+
+impl std::fmt::Display for PortableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortableError::Unsupported(type_name) => {
+                write!(f, "VM value of type '{}' cannot be serialized", type_name)
+            }
+            PortableError::Cyclic => write!(f, "cyclic VM value graphs cannot be serialized"),
+            PortableError::Malformed(message) => {
+                write!(f, "malformed serialized VM value: {}", message)
+            }
+        }
+    }
+}
+
+struct Capture {
+    nodes: Vec<PortableNode>,
+    indices: HashMap<XlangGCRef, usize>,
+    visiting: HashSet<XlangGCRef>,
+}
+
+impl Capture {
+    fn visit(&mut self, gc_ref: &XlangGCRef) -> Result<usize, PortableError> {
+        if let Some(index) = self.indices.get(gc_ref) {
+            return Ok(*index);
+        }
+        if !self.visiting.insert(gc_ref.clone()) {
+            return Err(PortableError::Cyclic);
+        }
+        let kind = if gc_ref.isinstance::<XlangVMInt>() {
+            PortableKind::Int(gc_ref.as_const_type::<XlangVMInt>().value)
+        } else if gc_ref.isinstance::<XlangVMFloat>() {
+            PortableKind::Float(gc_ref.as_const_type::<XlangVMFloat>().value)
+        } else if gc_ref.isinstance::<XlangVMString>() {
+            PortableKind::String(gc_ref.as_const_type::<XlangVMString>().value.clone())
+        } else if gc_ref.isinstance::<XlangVMBoolean>() {
+            PortableKind::Boolean(gc_ref.as_const_type::<XlangVMBoolean>().value)
+        } else if gc_ref.isinstance::<XlangVMNull>() {
+            PortableKind::Null
+        } else if gc_ref.isinstance::<XlangVMBytes>() {
+            PortableKind::Bytes(gc_ref.as_const_type::<XlangVMBytes>().value.clone())
+        } else if gc_ref.isinstance::<XlangVMRange>() {
+            let range = gc_ref.as_const_type::<XlangVMRange>();
+            PortableKind::Range(range.start, range.end)
+        } else if gc_ref.isinstance::<XlangVMKeyVal>() {
+            let keyval = gc_ref.as_const_type::<XlangVMKeyVal>();
+            PortableKind::KeyVal(self.visit(&keyval.key)?, self.visit(&keyval.value)?)
+        } else if gc_ref.isinstance::<XlangVMNamed>() {
+            let named = gc_ref.as_const_type::<XlangVMNamed>();
+            PortableKind::Named(self.visit(&named.key)?, self.visit(&named.value)?)
+        } else if gc_ref.isinstance::<XlangVMTuple>() {
+            let values = &gc_ref.as_const_type::<XlangVMTuple>().values;
+            let mut indices = Vec::with_capacity(values.len());
+            for value in values {
+                indices.push(self.visit(value)?);
+            }
+            PortableKind::Tuple(indices)
+        } else if gc_ref.isinstance::<XlangVMWrapper>() {
+            PortableKind::Wrapper(self.visit(&gc_ref.as_const_type::<XlangVMWrapper>().value_ref)?)
+        } else {
+            return Err(PortableError::Unsupported(vm_type_name(gc_ref)));
+        };
+        let alias = match try_const_alias_as_vmobject(&mut gc_ref.clone()) {
+            Ok(alias) => alias.clone(),
+            Err(mut e) => {
+                e.consume_ref();
+                Vec::new()
+            }
+        };
+        self.visiting.remove(gc_ref);
+        self.nodes.push(PortableNode { kind, alias });
+        self.indices.insert(gc_ref.clone(), self.nodes.len() - 1);
+        Ok(self.nodes.len() - 1)
+    }
+}
+
+impl PortableGraph {
+    pub(crate) fn capture(gc_ref: &XlangGCRef) -> Result<Self, PortableError> {
+        let mut capture = Capture {
+            nodes: Vec::new(),
+            indices: HashMap::new(),
+            visiting: HashSet::new(),
+        };
+        capture.visit(gc_ref)?;
+        Ok(PortableGraph {
+            version: PORTABLE_VERSION,
+            nodes: capture.nodes,
+        })
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("portable graph is always serializable")
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, PortableError> {
+        let graph: PortableGraph =
+            bincode::deserialize(data).map_err(|e| PortableError::Malformed(e.to_string()))?;
+        if graph.version != PORTABLE_VERSION {
+            return Err(PortableError::Malformed(format!(
+                "unsupported format version {}",
+                graph.version
+            )));
+        }
+        if graph.nodes.is_empty() {
+            return Err(PortableError::Malformed("empty value graph".to_string()));
+        }
+        // 子节点必须出现在父节点之前
+        for (position, node) in graph.nodes.iter().enumerate() {
+            let children = match &node.kind {
+                PortableKind::KeyVal(key, value) | PortableKind::Named(key, value) => {
+                    vec![*key, *value]
+                }
+                PortableKind::Tuple(values) => values.clone(),
+                PortableKind::Wrapper(value) => vec![*value],
+                _ => Vec::new(),
+            };
+            if children.iter().any(|child| *child >= position) {
+                return Err(PortableError::Malformed(format!(
+                    "node {} references a later node",
+                    position
+                )));
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Allocates the graph in `gc_system` and returns a new reference to the root.
+    pub(crate) fn build(&self, gc_system: &mut XlangGCSystem) -> XlangGCRef {
+        let mut built: Vec<XlangGCRef> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let mut gc_ref = match &node.kind {
+                PortableKind::Int(value) => gc_system.new_object(XlangVMInt::new(*value)),
+                PortableKind::Float(value) => gc_system.new_object(XlangVMFloat::new(*value)),
+                PortableKind::String(value) => gc_system.new_object(XlangVMString::new(value)),
+                PortableKind::Boolean(value) => gc_system.new_object(XlangVMBoolean::new(*value)),
+                PortableKind::Null => gc_system.new_object(XlangVMNull::new()),
+                PortableKind::Bytes(value) => gc_system.new_object(XlangVMBytes::new(value)),
+                PortableKind::Range(start, end) => {
+                    gc_system.new_object(XlangVMRange::new(*start, *end))
+                }
+                PortableKind::KeyVal(key, value) => {
+                    let mut key = built[*key].clone();
+                    let mut value = built[*value].clone();
+                    gc_system.new_object(XlangVMKeyVal::new(&mut key, &mut value))
+                }
+                PortableKind::Named(key, value) => {
+                    let mut key = built[*key].clone();
+                    let mut value = built[*value].clone();
+                    gc_system.new_object(XlangVMNamed::new(&mut key, &mut value))
+                }
+                PortableKind::Tuple(values) => {
+                    let values: Vec<XlangGCRef> =
+                        values.iter().map(|index| built[*index].clone().clone_ref()).collect();
+                    new_tuple_from_refs(values, gc_system)
+                }
+                PortableKind::Wrapper(value) => {
+                    let mut value = built[*value].clone();
+                    gc_system.new_object(XlangVMWrapper::new(&mut value))
+                }
+            };
+            if !node.alias.is_empty() {
+                match try_alias_as_vmobject(&mut gc_ref) {
+                    Ok(alias) => *alias = node.alias.clone(),
+                    Err(mut e) => e.consume_ref(),
+                }
+            }
+            built.push(gc_ref);
+        }
+        // 中间节点已被父节点引用，只保留根节点的原生引用
+        let root = built.pop().expect("value graph is never empty");
+        for mut gc_ref in built {
+            gc_ref.drop_ref();
+        }
+        root
+    }
+}
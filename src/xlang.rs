@@ -1,12 +1,14 @@
-use std::sync::Arc;
-
-use crate::arc_unsafe_refcell::Inner;
+use crate::buffer;
+use crate::heap;
+use colored::Colorize;
+use crate::lifetime::Liveness;
 use crate::{
-    extract_xlang_gc_ref_with_gc, extract_xlang_gc_ref_with_gc_arc, xlang_gc_ref_to_py_object,
-    ArcUnsafeRefCellWrapper, GCSystem, VMTuple, XlangCompilationError, XlangExecutionError,
+    borrow_error, check_same_heap, extract_xlang_gc_ref_with_gc, extract_xlang_gc_ref_with_gc_arc,
+    xlang_gc_ref_to_py_object, ArcUnsafeRefCellWrapper, GCSystem, VMTuple, VMValue,
+    XlangCompilationError, XlangExecutionError,
 };
 use pyo3::types::{PyDict, PyTuple};
-use pyo3::{exceptions::PyIOError, prelude::*};
+use pyo3::{exceptions::{PyIOError, PyTypeError}, prelude::*, PyClassInitializer, PyTraverseError, PyVisit};
 use xlang_frontend::{compile::build_code, dir_stack::DirStack};
 use xlang_vm_core::executor::vm::{VMCoroutinePool, VMError};
 use xlang_vm_core::gc::{GCRef, GCSystem as XlangGCSystem};
@@ -18,49 +20,93 @@ use xlang_vm_core::executor::variable::VMNamed as XLangVMNamed;
 use xlang_vm_core::executor::variable::VMNull as XLangVMNull;
 use xlang_vm_core::executor::variable::VMString as XLangVMString;
 use xlang_vm_core::executor::variable::{VMInstructions, VMTuple as XLangVMTuple};
-use xlang_vm_core::executor::variable::{VMLambda as XLangVMLambda, VMVariableError};
+use xlang_vm_core::executor::variable::{
+    VMCoroutineStatus, VMLambda as XLangVMLambda, VMVariableError,
+};
 
-#[pyclass(unsendable)]
-#[derive(Clone)]
+#[pyclass(extends=VMValue, unsendable)]
 pub struct Lambda {
-    gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
-    lambda_object: Option<GCRef>,
-    run_condition: Option<Arc<PyObject>>,
+    pub(crate) gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    pub(crate) lambda_object: Option<GCRef>,
+    pub(crate) liveness: Liveness,
+    pub(crate) run_condition: Option<PyObject>,
 }
 
 impl Lambda {
     pub(crate) fn create(gc: &mut GCSystem) -> Self {
         Lambda {
             lambda_object: None,
+            liveness: Liveness::default(),
             gc_system: gc.gc_system.clone(),
             run_condition: None,
         }
     }
-}
 
-impl Drop for Lambda {
-    fn drop(&mut self) {
-        if let Some(ref mut lambda) = self.lambda_object {
-            lambda.drop_ref();
+    // 包装脚本返回的 VMLambda，获得一个新的引用
+    pub(crate) fn wrap(
+        gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
+        lambda_object: GCRef,
+    ) -> Self {
+        Lambda {
+            lambda_object: Some(lambda_object),
+            liveness: Liveness::default(),
+            gc_system,
+            run_condition: None,
         }
     }
 }
 
+// lambda_object 的引用由 VMValue 基类持有并释放
+impl From<Lambda> for PyClassInitializer<Lambda> {
+    fn from(mut value: Lambda) -> Self {
+        value.liveness = Liveness::default();
+        let base = VMValue::create(
+            value.lambda_object.clone(),
+            value.liveness.clone(),
+            value.gc_system.clone(),
+        );
+        PyClassInitializer::from(base).add_subclass(value)
+    }
+}
+
+impl<'py> IntoPyObject<'py> for Lambda {
+    type Target = Lambda;
+    type Output = Bound<'py, Lambda>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+        Bound::new(py, self)
+    }
+}
+
 #[pymethods]
 impl Lambda {
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        if let Some(condition) = &self.run_condition {
+            visit.call(condition)?;
+        }
+        Ok(())
+    }
+
+    fn __clear__(&mut self) {
+        self.run_condition = None;
+    }
+
     #[new]
     fn new(gc: &mut GCSystem) -> Self {
         Lambda {
             lambda_object: None,
+            liveness: Liveness::default(),
             gc_system: gc.gc_system.clone(),
             run_condition: None,
         }
     }
 
     // 失败时返回错误信息
+    #[allow(clippy::too_many_arguments)]
     #[pyo3(signature = (code, default_args, capture=None, self_object=None, work_dir=None, run_condition=None))]
     fn load(
-        &mut self,
+        mut slf: PyRefMut<'_, Self>,
         code: &str,
         default_args: &mut VMTuple,
         capture: Option<PyObject>,
@@ -69,7 +115,10 @@ impl Lambda {
         run_condition: Option<PyObject>,
         py: Python<'_>,
     ) -> PyResult<()> {
-        let dir_stack = DirStack::new(Some(&work_dir.unwrap_or_else(|| ".").into()));
+        slf.liveness.check()?;
+        check_same_heap("default_args", &default_args.gc_system, &slf.gc_system)?;
+        let default_args_ref = default_args.gc_ref.get_mut()?;
+        let dir_stack = DirStack::new(Some(&work_dir.unwrap_or(".").into()));
         if dir_stack.is_err() {
             return Err(PyIOError::new_err(format!(
                 "Failed to create directory stack: {}",
@@ -78,7 +127,7 @@ impl Lambda {
         }
         let mut dir_stack = dir_stack.unwrap();
         let instruction_package;
-        match build_code(&code, &mut dir_stack) {
+        match build_code(code, &mut dir_stack) {
             Ok(package) => {
                 let mut translator = IRTranslator::new(&package);
                 let translate_result = translator.translate();
@@ -103,101 +152,98 @@ impl Lambda {
         let mut capture_ref_option: Option<GCRef> = if let Some(c) = capture {
             Some(extract_xlang_gc_ref_with_gc_arc(
                 &c.into_bound(py),
-                self.gc_system.clone(),
+                slf.gc_system.clone(),
             )?)
         } else {
             None
         };
 
-        let mut self_object_ref_option: Option<GCRef> = if let Some(s) = self_object {
-            Some(extract_xlang_gc_ref_with_gc_arc(
-                &s.into_bound(py),
-                self.gc_system.clone(),
-            )?)
-        } else {
-            None
+        let mut self_object_ref_option: Option<GCRef> = match self_object {
+            Some(s) => match extract_xlang_gc_ref_with_gc_arc(&s.into_bound(py), slf.gc_system.clone()) {
+                Ok(self_object_ref) => Some(self_object_ref),
+                Err(e) => {
+                    if let Some(capture_ref) = capture_ref_option.as_mut() {
+                        capture_ref.drop_ref();
+                    }
+                    return Err(e);
+                }
+            },
+            None => None,
         };
 
-        let mut default_result = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XLangVMNull::new()),
-            Err(e) => {
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to create default result: {}",
-                    e
-                )))
-            }
-        };
-        let mut instruction_ref = match self.gc_system.borrow_mut() {
+        // 三个对象在同一次借用中创建，借用失败时不会留下半成品
+        let lambda = match slf.gc_system.borrow_mut() {
             Ok(mut gc_system) => {
-                gc_system.new_object(VMInstructions::new(&instruction_package.unwrap()))
-            }
-            Err(e) => {
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to create instruction reference: {}",
-                    e
-                )))
-            }
-        };
-
-        let lambda: GCRef = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => gc_system.new_object(XLangVMLambda::new(
-                0,
-                "__main__".to_string(),
-                &mut default_args.gc_ref,
-                capture_ref_option.as_mut(),
-                self_object_ref_option.as_mut(),
-                &mut XLangVMLambdaBody::VMInstruction(instruction_ref.clone()),
-                &mut default_result,
-                false,
-            )),
-            Err(e) => {
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to create lambda object: {}",
-                    e
-                )))
+                let mut default_result = gc_system.new_object(XLangVMNull::new());
+                let mut instruction_ref =
+                    gc_system.new_object(VMInstructions::new(&instruction_package.unwrap()));
+                let lambda = gc_system.new_object(XLangVMLambda::new(
+                    0,
+                    "__main__".to_string(),
+                    default_args_ref,
+                    capture_ref_option.as_mut(),
+                    self_object_ref_option.as_mut(),
+                    &mut XLangVMLambdaBody::VMInstruction(instruction_ref.clone()),
+                    &mut default_result,
+                    false,
+                ));
+                default_result.drop_ref();
+                instruction_ref.drop_ref();
+                Ok(lambda)
             }
+            Err(e) => Err(borrow_error("Failed to create lambda object", e)),
         };
-
-        let mut old_ref = self.lambda_object.take();
-        self.lambda_object = Some(lambda);
-        if let Some(ref mut old_lambda) = old_ref {
-            old_lambda.drop_ref();
+        // 新建的 lambda 自己持有 capture 和 self_object
+        for extracted in capture_ref_option.iter_mut().chain(self_object_ref_option.iter_mut()) {
+            extracted.drop_ref();
         }
+        let lambda = lambda?;
 
-        default_result.drop_ref();
-        instruction_ref.drop_ref();
+        // 旧的 lambda 由基类释放
+        slf.lambda_object = Some(lambda.clone());
+        slf.as_super().replace_ref(Some(lambda))?;
 
-        self.run_condition = match run_condition {
-            Some(condition_func) => Some(Arc::new(condition_func)),
-            None => None,
-        };
-        return Ok(());
+        slf.run_condition = run_condition;
+        Ok(())
     }
 
     #[pyo3(signature = (args = None, kwargs=None))]
     fn __call__(
-        &mut self,
+        slf: &Bound<'_, Self>,
         args: Option<Vec<PyObject>>,
         kwargs: Option<Bound<'_, PyDict>>,
         py: Python<'_>,
     ) -> PyResult<PyObject> {
-        if self.lambda_object.is_none() {
-            return Err(XlangExecutionError::new_err(format!(
-                "Lambda object is not initialized"
-            )));
+        // 回调中再次调用正在执行的同一个 lambda
+        let mut guard = slf.try_borrow_mut().map_err(|_| {
+            XlangExecutionError::new_err("Lambda is already running and cannot be called re-entrantly")
+        })?;
+        let this = &mut *guard;
+        this.liveness.check()?;
+        if this.lambda_object.is_none() {
+            return Err(XlangExecutionError::new_err("Lambda object is not initialized"));
         }
+        // 调用结束（包括出错返回）时按回收策略处理
+        let _call = heap::enter_call(&this.gc_system);
+        // 脚本可能替换字节串的存储，先把已导出的存储从虚拟机中取出
+        let _run = buffer::enter_call();
         // 使用空向量作为默认值
-        let args_vec_ref = args.unwrap_or_else(|| vec![]);
+        let args_vec_ref = args.unwrap_or_default();
         let mut args_vec = Vec::with_capacity(args_vec_ref.len());
 
         for arg in args_vec_ref.iter() {
-            args_vec.push(extract_xlang_gc_ref_with_gc_arc(
-                &arg.extract::<PyObject>(py).unwrap().into_bound(py),
-                self.gc_system.clone(),
-            )?);
+            match extract_xlang_gc_ref_with_gc_arc(arg.bind(py), this.gc_system.clone()) {
+                Ok(arg_ref) => args_vec.push(arg_ref),
+                Err(e) => {
+                    for arg in args_vec.iter_mut() {
+                        arg.drop_ref();
+                    }
+                    return Err(e);
+                }
+            }
         }
 
-        let mut arg_tuple = match self.gc_system.borrow_mut() {
+        let mut arg_tuple = match this.gc_system.borrow_mut() {
             Ok(mut gc_system) => {
                 gc_system.new_object(XLangVMTuple::new(&mut args_vec.iter_mut().collect()))
             }
@@ -205,37 +251,50 @@ impl Lambda {
                 for arg in args_vec.iter_mut() {
                     arg.drop_ref();
                 }
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to create argument tuple: {}",
-                    e
-                )));
+                return Err(borrow_error("Failed to create argument tuple", e));
             }
         };
 
         if let Some(kwargs) = kwargs {
             for (key, value) in kwargs.iter() {
-                let mut key_str = match self.gc_system.borrow_mut() {
-                    Ok(mut gc_system) => {
-                        gc_system.new_object(XLangVMString::new(&key.extract::<String>().unwrap()))
+                let key = match key.extract::<String>() {
+                    Ok(key) => key,
+                    Err(_) => {
+                        for arg in args_vec.iter_mut() {
+                            arg.drop_ref();
+                        }
+                        arg_tuple.drop_ref();
+                        return Err(PyTypeError::new_err(format!(
+                            "keyword argument names must be strings, not {}",
+                            key.get_type().name()?
+                        )));
                     }
+                };
+                let mut key_str = match this.gc_system.borrow_mut() {
+                    Ok(mut gc_system) => gc_system.new_object(XLangVMString::new(&key)),
                     Err(e) => {
                         for arg in args_vec.iter_mut() {
                             arg.drop_ref();
                         }
                         arg_tuple.drop_ref();
-                        return Err(XlangExecutionError::new_err(format!(
-                            "Failed to create key string: {}",
-                            e
-                        )));
+                        return Err(borrow_error("Failed to create key string", e));
                     }
                 };
 
-                let mut value_ref = extract_xlang_gc_ref_with_gc_arc(
-                    &value.extract::<PyObject>().unwrap().into_bound(py),
-                    self.gc_system.clone(),
-                )?;
+                let mut value_ref =
+                    match extract_xlang_gc_ref_with_gc_arc(&value, this.gc_system.clone()) {
+                        Ok(value_ref) => value_ref,
+                        Err(e) => {
+                            for arg in args_vec.iter_mut() {
+                                arg.drop_ref();
+                            }
+                            arg_tuple.drop_ref();
+                            key_str.drop_ref();
+                            return Err(e);
+                        }
+                    };
 
-                let mut keyval = match self.gc_system.borrow_mut() {
+                let mut keyval = match this.gc_system.borrow_mut() {
                     Ok(mut gc_system) => {
                         gc_system.new_object(XLangVMNamed::new(&mut key_str, &mut value_ref))
                     }
@@ -246,10 +305,7 @@ impl Lambda {
                         arg_tuple.drop_ref();
                         key_str.drop_ref();
                         value_ref.drop_ref();
-                        return Err(XlangExecutionError::new_err(format!(
-                            "Failed to create named value: {}",
-                            e
-                        )));
+                        return Err(borrow_error("Failed to create named value", e));
                     }
                 };
 
@@ -266,9 +322,7 @@ impl Lambda {
                     }
                     arg_tuple.drop_ref();
                     keyval.drop_ref();
-                    return Err(XlangExecutionError::new_err(format!(
-                        "Failed to append keyval to tuple"
-                    )));
+                    return Err(XlangExecutionError::new_err("Failed to append keyval to tuple"));
                 }
                 keyval.drop_ref();
             }
@@ -276,8 +330,21 @@ impl Lambda {
 
         let mut coroutine_pool = VMCoroutinePool::new(true);
 
-        let assgined = match self.gc_system.borrow_mut() {
-            Ok(mut gc_system) => self
+        // 协程结束后状态停留在 Finished，重置后才能再次调用同一个 lambda
+        let lambda = this
+            .lambda_object
+            .as_mut()
+            .unwrap()
+            .as_type::<XLangVMLambda>();
+        if matches!(
+            lambda.coroutine_status,
+            VMCoroutineStatus::Finished | VMCoroutineStatus::Crashed
+        ) {
+            lambda.coroutine_status = VMCoroutineStatus::Running;
+        }
+
+        let assgined = match this.gc_system.borrow_mut() {
+            Ok(mut gc_system) => this
                 .lambda_object
                 .as_mut()
                 .unwrap()
@@ -290,10 +357,7 @@ impl Lambda {
                     arg.drop_ref();
                 }
                 arg_tuple.drop_ref();
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to borrow GC system for arguments assignment: {}",
-                    e
-                )));
+                return Err(borrow_error("Failed to borrow GC system for arguments assignment", e));
             }
         };
 
@@ -313,9 +377,9 @@ impl Lambda {
         }
         let mut assgined = assgined.unwrap();
 
-        let coro_id = match self.gc_system.borrow_mut() {
+        let coro_id = match this.gc_system.borrow_mut() {
             Ok(mut gc_system) => coroutine_pool.new_coroutine(
-                &mut self.lambda_object.as_mut().unwrap().clone_ref(),
+                &mut this.lambda_object.as_mut().unwrap().clone_ref(),
                 &mut assgined,
                 &mut gc_system,
             ),
@@ -323,10 +387,7 @@ impl Lambda {
                 for arg in args_vec.iter_mut() {
                     arg.drop_ref();
                 }
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to borrow GC system for coroutine creation: {}",
-                    e
-                )));
+                return Err(borrow_error("Failed to borrow GC system for coroutine creation", e));
             }
         };
 
@@ -345,38 +406,14 @@ impl Lambda {
         }
         let _coro_id = coro_id.unwrap();
 
-        let result = unsafe {
-            coroutine_pool.run_while(self.gc_system.get_mut(), |_| {
-                // 使用 run_condition 函数检查
-                if let Some(ref condition) = self.run_condition {
-                    match condition.call1(py, ()) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            return Err(VMError::DetailedError(
-                                format!("Run condition function failed: {}", e),
-                            ));
-                        }
-                    }
-                }
-                Ok(())
-            })
-        };
-
-        if result.is_err() {
-            return Err(XlangExecutionError::new_err(format!(
-                "Failed to run coroutine: {}",
-                {
-                    let mut e = result.err().unwrap();
-                    e.consume_ref();
-                    for arg in args_vec.iter_mut() {
-                        arg.drop_ref();
-                    }
-                    e.to_string()
-                }
-            )));
+        if let Err(e) = run_coroutines(&mut coroutine_pool, &this.gc_system, this.run_condition.as_ref(), py) {
+            for arg in args_vec.iter_mut() {
+                arg.drop_ref();
+            }
+            return Err(e);
         }
 
-        let result = self
+        let result = this
             .lambda_object
             .as_mut()
             .unwrap()
@@ -387,16 +424,28 @@ impl Lambda {
             arg.drop_ref();
         }
 
-        let py_object = xlang_gc_ref_to_py_object(result, self.gc_system.clone(), py)?;
+        let py_object = xlang_gc_ref_to_py_object(result, this.gc_system.clone(), py)?;
+
+        // 返回的闭包继承调用者的 run_condition
+        if let Ok(returned) = py_object.bind(py).downcast::<Lambda>() {
+            let mut returned = returned.borrow_mut();
+            if returned.run_condition.is_none() {
+                returned.run_condition = this.run_condition.as_ref().map(|c| c.clone_ref(py));
+            }
+        }
 
         Ok(py_object)
     }
 
+    #[pyo3(signature = (run_condition=None))]
+    fn set_run_condition(&mut self, run_condition: Option<PyObject>) {
+        self.run_condition = run_condition;
+    }
+
     fn __repr__(&self, _py: Python<'_>) -> PyResult<String> {
+        self.liveness.check()?;
         if self.lambda_object.is_none() {
-            return Err(XlangExecutionError::new_err(format!(
-                "Lambda object is not initialized"
-            )));
+            return Err(XlangExecutionError::new_err("Lambda object is not initialized"));
         }
         let lambda = self.lambda_object.as_ref().unwrap();
         let repr = format!("<xlang lambda object at {:p}>", lambda);
@@ -404,35 +453,153 @@ impl Lambda {
     }
 }
 
-#[pyclass(unsendable)]
+// 与 VMCoroutinePool::run_while 相同的循环，但每一步单独借用堆：两步之间不持有任何借用，
+// 回收和 run_condition 可以正常使用堆；步进中的 Python 回调经由 lend 使用虚拟机传入的借用
+fn run_coroutines(
+    coroutine_pool: &mut VMCoroutinePool,
+    gc_system: &ArcUnsafeRefCellWrapper<XlangGCSystem>,
+    run_condition: Option<&PyObject>,
+    py: Python<'_>,
+) -> PyResult<()> {
+    loop {
+        heap::step(gc_system);
+        // 使用 run_condition 函数检查
+        if let Some(condition) = run_condition {
+            if let Err(e) = condition.call1(py, ()) {
+                clean_coroutines(coroutine_pool);
+                return Err(run_error(VMError::DetailedError(format!(
+                    "Run condition function failed: {}",
+                    e
+                ))));
+            }
+        }
+        let mut heap_guard = match gc_system.borrow_mut() {
+            Ok(heap_guard) => heap_guard,
+            Err(e) => {
+                clean_coroutines(coroutine_pool);
+                return Err(borrow_error("Failed to borrow GC system for execution", e));
+            }
+        };
+        let spawned_coroutines = match coroutine_pool.step_all(&mut heap_guard) {
+            Ok(spawned_coroutines) => spawned_coroutines,
+            Err((_, e)) => {
+                let e = dump_step_error(coroutine_pool, e);
+                clean_coroutines(coroutine_pool);
+                return Err(run_error(e));
+            }
+        };
+        coroutine_pool.sweep_finished();
+        if let Some(mut coroutines) = spawned_coroutines {
+            for i in 0..coroutines.len() {
+                let coroutine = &mut coroutines[i];
+                if let Err(e) = coroutine_pool.new_coroutine(
+                    &mut coroutine.lambda_ref,
+                    &mut coroutine.args,
+                    &mut heap_guard,
+                ) {
+                    // 失败的和尚未启动的协程仍持有各自的引用
+                    for coroutine in coroutines[i..].iter_mut() {
+                        coroutine.lambda_ref.drop_ref();
+                        coroutine.args.drop_ref();
+                    }
+                    clean_coroutines(coroutine_pool);
+                    return Err(run_error(e));
+                }
+            }
+        }
+        drop(heap_guard);
+        if coroutine_pool.executors.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+fn clean_coroutines(coroutine_pool: &mut VMCoroutinePool) {
+    for (executor, _) in coroutine_pool.executors.iter_mut() {
+        executor.clean();
+    }
+}
+
+fn run_error(mut e: VMError) -> PyErr {
+    e.consume_ref();
+    XlangExecutionError::new_err(format!("Failed to run coroutine: {}", e.to_string()))
+}
+
+// 与 run_while 的输出保持一致，附带所有协程的上下文
+fn dump_step_error(coroutine_pool: &mut VMCoroutinePool, mut e: VMError) -> VMError {
+    if !coroutine_pool.enable_dump {
+        return e;
+    }
+    let contexts = coroutine_pool
+        .executors
+        .iter_mut()
+        .map(|(executor, _)| {
+            let lambda = executor.entry_lambda.as_const_type::<XLangVMLambda>();
+            format!(
+                "{}\n{}\n\n{}\n\n{}",
+                format!("-> {}: {}", lambda.signature, lambda.coroutine_status.to_string())
+                    .bright_yellow()
+                    .bold(),
+                executor.context.format_context(&mut executor.stack),
+                "=== Code ===".bright_blue().bold(),
+                executor.repr_current_code(Some(2))
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    let dumped = VMError::DetailedError(format!(
+        "{}\n\n{}\n{}\n\nAll Coroutine Contexts:\n{}",
+        "** CoroutinePool Step Error! **".bright_red().bold(),
+        "# Main Error".bright_red().bold().underline(),
+        e.to_string().red(),
+        contexts
+    ));
+    e.consume_ref();
+    dumped
+}
+
+#[pyclass(extends=VMValue, unsendable)]
 #[derive(Clone)]
 pub struct WrappedPyFunction {
     pub(crate) gc_system: ArcUnsafeRefCellWrapper<XlangGCSystem>,
     pub(crate) function_object: Option<GCRef>,
-    pub(crate) callable_ref: Option<Arc<PyObject>>,
+    pub(crate) liveness: Liveness,
 }
 
 impl WrappedPyFunction {
     pub(crate) fn create(gc: &mut GCSystem) -> Self {
         WrappedPyFunction {
             function_object: None,
+            liveness: Liveness::default(),
             gc_system: gc.gc_system.clone(),
-            callable_ref: None,
         }
     }
 }
 
-impl Drop for WrappedPyFunction {
-    fn drop(&mut self) {
-        if let Some(ref mut function) = self.function_object {
-            function.drop_ref();
-        }
+impl From<WrappedPyFunction> for PyClassInitializer<WrappedPyFunction> {
+    fn from(mut value: WrappedPyFunction) -> Self {
+        value.liveness = Liveness::default();
+        let base = VMValue::create(
+            value.function_object.clone(),
+            value.liveness.clone(),
+            value.gc_system.clone(),
+        );
+        PyClassInitializer::from(base).add_subclass(value)
+    }
+}
+
+impl<'py> IntoPyObject<'py> for WrappedPyFunction {
+    type Target = WrappedPyFunction;
+    type Output = Bound<'py, WrappedPyFunction>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+        Bound::new(py, self)
     }
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 struct PackedCallableContext {
-    callable_ref: usize, // 使用整数代替裸指针
-    gc_arc: usize,       // 使用整数代替裸指针
+    callable_id: usize, // 堆状态中可调用对象的编号，可调用对象由堆持有
 }
 
 #[pymethods]
@@ -441,30 +608,26 @@ impl WrappedPyFunction {
     fn new(gc: &mut GCSystem) -> Self {
         WrappedPyFunction {
             function_object: None,
+            liveness: Liveness::default(),
             gc_system: gc.gc_system.clone(),
-            callable_ref: None,
         }
     }
 
     fn wrap(
-        &mut self,
+        mut slf: PyRefMut<'_, Self>,
         py_callable: PyObject,
         default_args: &mut VMTuple,
         _py: Python<'_>,
     ) -> PyResult<()> {
+        check_same_heap("default_args", &default_args.gc_system, &slf.gc_system)?;
+        let default_args_ref = default_args.gc_ref.get_mut()?;
         // 释放旧引用(如果有的话)
-        if let Some(ref mut old_function) = self.function_object {
-            old_function.drop_ref();
-            self.function_object = None;
-        }
+        slf.as_super().replace_ref(None)?;
+        slf.function_object = None;
 
-        // 将Python可调用对象存储在Arc中以安全地在多个地方共享
-        self.callable_ref = Some(Arc::new(py_callable));
-        // 创建上下文并序列化为字节
-        let context = PackedCallableContext {
-            callable_ref: Arc::as_ptr(self.callable_ref.as_ref().unwrap()) as usize, // 将Arc的裸指针存储为整数
-            gc_arc: self.gc_system.get_inner() as usize,
-        };
+        // 可调用对象交给堆持有，CPython 通过 HeapRoots 看到这条引用
+        let callable_id = heap::add_callable(&slf.gc_system, py_callable);
+        let context = PackedCallableContext { callable_id };
         let serialized_context = bincode::serialize(&context).unwrap();
 
         // 定义静态函数
@@ -491,17 +654,32 @@ impl WrappedPyFunction {
             let capture_bytes = capture_ref.as_type::<XLangVMBytes>();
 
             // 解包上下文
-            let context: PackedCallableContext =
-                bincode::deserialize(&capture_bytes.value).unwrap();
-
-            // 重新构造Arc
-            let callable_ref = unsafe { Arc::from_raw(context.callable_ref as *const PyObject) };
-            std::mem::forget(callable_ref.clone()); // 防止调用时释放
-
-            let gc_system_arc =
-                ArcUnsafeRefCellWrapper::from_inner(context.gc_arc as *mut Inner<XlangGCSystem>);
+            let context: PackedCallableContext = match bincode::deserialize(&capture_bytes.value)
+            {
+                Ok(context) => context,
+                Err(_) => {
+                    return Err(VMVariableError::TypeError(
+                        args.clone_ref(),
+                        "corrupted captured context".to_string(),
+                    ))
+                }
+            };
 
             Python::with_gil(|py| {
+                let (gc_system_arc, callable) =
+                    match heap::callable(gc_system, context.callable_id, py) {
+                        Some((gc_system_arc, Some(callable))) => (gc_system_arc, callable),
+                        Some((_, None)) => {
+                            return Err(VMVariableError::DetailedError(
+                                "Python callable has been released".to_string(),
+                            ))
+                        }
+                        None => {
+                            return Err(VMVariableError::DetailedError(
+                                "wrapped Python function called outside its GCSystem".to_string(),
+                            ))
+                        }
+                    };
                 // 确保参数是一个元组
                 if !args.isinstance::<XLangVMTuple>() {
                     return Err(VMVariableError::TypeError(
@@ -510,113 +688,115 @@ impl WrappedPyFunction {
                     ));
                 }
 
-                // 获取传递给函数的参数列表
-                let args_tuple = args.as_type::<XLangVMTuple>();
-                let mut py_args = Vec::new();
-
-                let py_kwargs = PyDict::new(py);
-
-                for arg_ref in &mut args_tuple.values {
-                    if arg_ref.isinstance::<XLangVMNamed>()
-                        && arg_ref
-                            .as_const_type::<XLangVMNamed>()
-                            .key
-                            .isinstance::<XLangVMString>()
-                    {
-                        // 解包 VMNamed 为键值对
-                        let named = arg_ref.as_type::<XLangVMNamed>();
-                        let key = named.key.as_type::<XLangVMString>().value.clone();
-                        let py_value = match xlang_gc_ref_to_py_object(
-                            &mut named.value,
-                            gc_system_arc.clone(),
-                            py,
-                        ) {
-                            Ok(obj) => obj,
-                            Err(e) => {
-                                return Err(VMVariableError::DetailedError(format!(
-                                    "Failed to convert VMNamed value to Python: {}",
-                                    e
-                                )));
-                            }
-                        };
-                        // 将命名参数添加到kwargs字典中
-                        if let Err(e) = py_kwargs.set_item(key, py_value) {
-                            return Err(VMVariableError::DetailedError(format!(
-                                "Failed to set keyword argument: {}",
-                                e
-                            )));
-                        }
-                    } else {
-                        // 普通位置参数保持不变
-                        let py_arg =
-                            match xlang_gc_ref_to_py_object(arg_ref, gc_system_arc.clone(), py) {
+                // 回调期间把虚拟机传入的可变借用借给 Python 代码，回调中对堆的访问都经由它再借用
+                let py_result = gc_system_arc.lend(gc_system, || {
+                    // 获取传递给函数的参数列表
+                    let args_tuple = args.as_type::<XLangVMTuple>();
+                    let mut py_args = Vec::new();
+
+                    let py_kwargs = PyDict::new(py);
+
+                    for arg_ref in &mut args_tuple.values {
+                        if arg_ref.isinstance::<XLangVMNamed>()
+                            && arg_ref
+                                .as_const_type::<XLangVMNamed>()
+                                .key
+                                .isinstance::<XLangVMString>()
+                        {
+                            // 解包 VMNamed 为键值对
+                            let named = arg_ref.as_type::<XLangVMNamed>();
+                            let key = named.key.as_type::<XLangVMString>().value.clone();
+                            let py_value = match xlang_gc_ref_to_py_object(
+                                &mut named.value,
+                                gc_system_arc.clone(),
+                                py,
+                            ) {
                                 Ok(obj) => obj,
                                 Err(e) => {
                                     return Err(VMVariableError::DetailedError(format!(
-                                        "Failed to convert argument to Python: {}",
+                                        "Failed to convert VMNamed value to Python: {}",
                                         e
                                     )));
                                 }
                             };
-                        py_args.push(py_arg);
+                            // 将命名参数添加到kwargs字典中
+                            if let Err(e) = py_kwargs.set_item(key, py_value) {
+                                return Err(VMVariableError::DetailedError(format!(
+                                    "Failed to set keyword argument: {}",
+                                    e
+                                )));
+                            }
+                        } else {
+                            // 普通位置参数保持不变
+                            let py_arg =
+                                match xlang_gc_ref_to_py_object(arg_ref, gc_system_arc.clone(), py) {
+                                    Ok(obj) => obj,
+                                    Err(e) => {
+                                        return Err(VMVariableError::DetailedError(format!(
+                                            "Failed to convert argument to Python: {}",
+                                            e
+                                        )));
+                                    }
+                                };
+                            py_args.push(py_arg);
+                        }
                     }
-                }
 
-                // 创建位置参数元组
-                let py_tuple = match PyTuple::new(py, &py_args) {
-                    Ok(tuple) => tuple,
+                    // 创建位置参数元组
+                    let py_tuple = match PyTuple::new(py, &py_args) {
+                        Ok(tuple) => tuple,
+                        Err(e) => {
+                            return Err(VMVariableError::DetailedError(format!(
+                                "Failed to create Python tuple: {}",
+                                e
+                            )));
+                        }
+                    };
+                    callable.call(py, py_tuple, Some(&py_kwargs)).map_err(|e| {
+                        VMVariableError::DetailedError(format!("Python function call failed: {}", e))
+                    })
+                });
+                let py_result = match py_result {
+                    Ok(py_result) => py_result?,
                     Err(e) => {
                         return Err(VMVariableError::DetailedError(format!(
-                            "Failed to create Python tuple: {}",
+                            "Failed to lend GC system to Python callback: {}",
                             e
-                        )));
+                        )))
                     }
                 };
-                match callable_ref.call(py, py_tuple, Some(&py_kwargs)) {
-                    Ok(py_result) => {
-                        let bound_result = py_result.into_bound(py);
-                        extract_xlang_gc_ref_with_gc(&bound_result, gc_system).map_err(|e| {
-                            VMVariableError::DetailedError(format!(
-                                "Failed to convert Python result to XLang: {}",
-                                e
-                            ))
-                        })
-                    }
-                    Err(e) => Err(VMVariableError::DetailedError(format!(
-                        "Python function call failed: {}",
+                extract_xlang_gc_ref_with_gc(py_result.bind(py), gc_system).map_err(|e| {
+                    VMVariableError::DetailedError(format!(
+                        "Failed to convert Python result to XLang: {}",
                         e
-                    ))),
-                }
+                    ))
+                })
             })
         }
 
         // 创建一个新的XLang函数对象
-        let mut packed_context = match self.gc_system.borrow_mut() {
+        let mut packed_context = match slf.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XLangVMBytes::new(&serialized_context)),
             Err(e) => {
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to borrow GC system for context creation: {}",
-                    e
-                )));
+                heap::remove_callable(&slf.gc_system, callable_id);
+                return Err(borrow_error("Failed to borrow GC system for context creation", e));
             }
         };
 
-        let mut default_result = match self.gc_system.borrow_mut() {
+        let mut default_result = match slf.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XLangVMNull::new()),
             Err(e) => {
                 packed_context.drop_ref();
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to borrow GC system for default result creation: {}",
-                    e
-                )));
+                heap::remove_callable(&slf.gc_system, callable_id);
+                return Err(borrow_error("Failed to borrow GC system for default result creation", e));
             }
         };
 
-        let function_object = match self.gc_system.borrow_mut() {
+        let function_object = match slf.gc_system.borrow_mut() {
             Ok(mut gc_system) => gc_system.new_object(XLangVMLambda::new(
                 0,
                 "<python>".to_string(),
-                &mut default_args.gc_ref,
+                default_args_ref,
                 Some(&mut packed_context),
                 None,
                 &mut XLangVMLambdaBody::VMNativeFunction(py_function_static),
@@ -626,26 +806,26 @@ impl WrappedPyFunction {
             Err(e) => {
                 packed_context.drop_ref();
                 default_result.drop_ref();
-                return Err(XlangExecutionError::new_err(format!(
-                    "Failed to borrow GC system for function creation: {}",
-                    e
-                )));
+                heap::remove_callable(&slf.gc_system, callable_id);
+                return Err(borrow_error("Failed to borrow GC system for function creation", e));
             }
         };
 
         default_result.drop_ref();
         packed_context.drop_ref();
 
-        self.function_object = Some(function_object);
+        // 函数对象被回收时释放可调用对象
+        heap::bind_callable(&slf.gc_system, callable_id, &function_object);
+        slf.function_object = Some(function_object.clone());
+        slf.as_super().replace_ref(Some(function_object))?;
 
         Ok(())
     }
 
     fn __repr__(&self, _py: Python<'_>) -> PyResult<String> {
+        self.liveness.check()?;
         if self.function_object.is_none() {
-            return Err(XlangExecutionError::new_err(format!(
-                "Function object is not initialized"
-            )));
+            return Err(XlangExecutionError::new_err("Function object is not initialized"));
         }
         let function = self.function_object.as_ref().unwrap();
         let repr = format!("<xlang wrapped function object at {:p}>", function);
@@ -1,16 +1,79 @@
-from typing import Optional, overload
+from typing import Any, Callable, ClassVar, Dict, Iterator, List, Literal, Optional, Type, TypeVar, Union, overload
+
+_F = TypeVar("_F", bound=Callable[..., Any])
+_V = TypeVar("_V", bound=VMValue)
 
 class GCSystem:
-    def collect(self) -> None: ...
+    warn_on_leak: bool
+    def __init__(self, warn_on_leak: bool = False) -> None:
+        """With warn_on_leak, dropping the last GCSystem of a heap while Python
+        wrappers still reference its values emits a ResourceWarning."""
+        ...
+    def leak_check(self) -> LeakCheck:
+        """On exit, collects and raises XlangLeakError listing objects that
+        appeared inside the block and are still alive."""
+        ...
+    def scope(self) -> Scope:
+        """Values created inside the block are released on exit unless passed
+        to Scope.keep(). Also usable as a decorator."""
+        ...
+    def collect(self) -> int:
+        """Inside a Lambda call, e.g. from a wrapped Python callback, the
+        collection is deferred until the outermost call returns and 0 is
+        returned."""
+        ...
     def object_count(self) -> int: ...
+    def borrow_state(self) -> Dict[str, Any]:
+        """shared and mutable borrow counts, debug, and holders ({"kind",
+        "location", "lent", "python_stack"}) for borrows taken while
+        debug_borrows() is on."""
+        ...
+    def stats(self) -> Dict[str, Any]:
+        """live_objects, approx_bytes, by_type ({type: {"count", "bytes"}}),
+        collections, last_freed, total_freed, collect_time in seconds, by_trigger
+        (manual, allocation, call, step) and last_trigger."""
+        ...
+    def set_policy(
+        self,
+        allocation_threshold: Optional[int] = 10000,
+        collect_after_call: bool = False,
+        step_interval: Optional[int] = None,
+    ) -> None:
+        """Automatic collections run only at outermost Lambda call boundaries
+        and between execution steps. Omitted arguments reset to the defaults;
+        None disables that trigger."""
+        ...
+    def get_policy(self) -> Dict[str, Any]: ...
+    def paused(self) -> CollectionPause: ...
+    def live_objects(self, type: Optional[Union[str, Type[VMValue]]] = None) -> List[VMValue]:
+        """type may be a typeof name such as "string" or a wrapper class such as VMString."""
+        ...
+    def weak(self, value: VMValue) -> WeakHandle:
+        """Handle that does not keep value alive; calling it returns None once
+        a collection has freed the object."""
+        ...
+    def adopt(self, value: _V) -> _V:
+        """Deep-copies a value owned by another GCSystem into this one. Values
+        already owned by this GCSystem are returned unchanged."""
+        ...
+    def referrers(self, value: VMValue) -> List[VMValue]: ...
+    def referents(self, value: VMValue) -> List[VMValue]: ...
+    def snapshot(self) -> HeapSnapshot: ...
     def new_int(self, value: int) -> VMInt: ...
     def new_float(self, value: float) -> VMFloat: ...
     def new_string(self, value: str) -> VMString: ...
     def new_null(self) -> VMNull: ...
-    def new_bytes(self, value: bytes) -> VMBytes: ...
+    def new_boolean(self, value: bool) -> VMBoolean: ...
+    def new_bytes(self, value: bytes | bytearray | memoryview) -> VMBytes: ...
     def new_keyval(self, key: object, value: object) -> VMKeyVal: ...
     def new_named(self, name: object, value: object) -> VMNamed: ...
     def new_tuple(self, values: list) -> VMTuple: ...
+    def from_json(
+        self, data: str | bytes, object_pairs: Literal["keyval", "named"] = "keyval"
+    ) -> VMValue:
+        """Objects become tuples of VMKeyVal (or VMNamed) with VMString keys.
+        An object whose only key is "$bytes" decodes its base64 string to VMBytes."""
+    def parse_literal(self, text: str) -> VMValue: ...
     def new_wrapper(self, value: object) -> VMWrapper: ...
     def new_range(self, start: int, end: int) -> VMRange: ...
     def new_lambda(self, code: str, default_args: VMTuple) -> Lambda: ...
@@ -18,66 +81,298 @@ class GCSystem:
         self, func: callable, default_args: VMTuple
     ) -> WrappedPyFunction: ...
 
-class VMInt:
+class LeakCheck:
+    def __enter__(self) -> LeakCheck: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+
+class WeakHandle:
+    def __call__(self) -> Optional[VMValue]: ...
+    @property
+    def alive(self) -> bool: ...
+
+class Scope:
+    def __enter__(self) -> Scope: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+    def keep(self, value: _V) -> _V:
+        """Hands value to the enclosing scope, or to the wrapper itself."""
+        ...
+    def __call__(self, func: _F) -> _F:
+        """Runs each call in a new scope; returned values (also nested in
+        returned tuples or lists) are kept."""
+        ...
+
+class CollectionPause:
+    def __enter__(self) -> CollectionPause: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+
+class HeapSnapshot:
+    def __len__(self) -> int: ...
+    def __contains__(self, value: VMValue) -> bool: ...
+    def diff(self, other: HeapSnapshot) -> List[VMValue]:
+        """Objects present in other but not in self that are still alive."""
+        ...
+
+class VMValue:
+    def __init__(self, gc: GCSystem, value: object) -> None:
+        """Wraps value in gc; VM values share the same heap object, other
+        Python objects are converted first. Intended for Python subclasses."""
+    @property
+    def type_name(self) -> str: ...
+    @property
+    def refcount(self) -> int: ...
+    @property
+    def gc(self) -> GCSystem: ...
+    def to_py(self) -> object: ...
+    def clone(self) -> VMValue: ...
+    def deepcopy(self) -> VMValue: ...
+    def __copy__(self) -> VMValue: ...
+    def __deepcopy__(self, memo: dict) -> VMValue: ...
+    def __reduce__(self) -> tuple: ...
+    def to_source(self) -> str: ...
+    def to_json(self, indent: Optional[int] = None, sort_keys: bool = False) -> str:
+        """Tuples of string-keyed VMKeyVal/VMNamed become objects, other tuples
+        arrays. VMBytes is written as {"$bytes": "<standard base64>"}."""
+    def same_object(self, other: object) -> bool: ...
+    def id(self) -> int: ...
+    def __sizeof__(self) -> int: ...
+    def release(self) -> None:
+        """Drops the reference now; any later use raises ReferenceError."""
+        ...
+    @property
+    def released(self) -> bool: ...
+
+class VMInt(VMValue):
     def __init__(self, gc: GCSystem, value: int) -> None: ...
     def get_value(self) -> int: ...
     def set_value(self, value: int) -> None: ...
+    def __add__(self, other: object) -> object: ...
+    def __radd__(self, other: object) -> object: ...
+    def __sub__(self, other: object) -> object: ...
+    def __rsub__(self, other: object) -> object: ...
+    def __mul__(self, other: object) -> object: ...
+    def __rmul__(self, other: object) -> object: ...
+    def __truediv__(self, other: object) -> object: ...
+    def __rtruediv__(self, other: object) -> object: ...
+    def __mod__(self, other: object) -> object: ...
+    def __rmod__(self, other: object) -> object: ...
+    def __pow__(self, other: object, modulo: None = None) -> object: ...
+    def __rpow__(self, other: object) -> object: ...
+    def __neg__(self) -> object: ...
+    def __lt__(self, other: object) -> bool: ...
+    def __le__(self, other: object) -> bool: ...
+    def __gt__(self, other: object) -> bool: ...
+    def __ge__(self, other: object) -> bool: ...
+    def __bool__(self) -> bool: ...
+    def __int__(self) -> int: ...
+    def __float__(self) -> float: ...
+    def __and__(self, other: object) -> object: ...
+    def __rand__(self, other: object) -> object: ...
+    def __or__(self, other: object) -> object: ...
+    def __ror__(self, other: object) -> object: ...
+    def __xor__(self, other: object) -> object: ...
+    def __rxor__(self, other: object) -> object: ...
+    def __lshift__(self, other: object) -> object: ...
+    def __rlshift__(self, other: object) -> object: ...
+    def __rshift__(self, other: object) -> object: ...
+    def __rrshift__(self, other: object) -> object: ...
+    def __invert__(self) -> object: ...
+    def __index__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
 
-class VMFloat:
+class VMFloat(VMValue):
     def __init__(self, gc: GCSystem, value: float) -> None: ...
     def get_value(self) -> float: ...
     def set_value(self, value: float) -> None: ...
+    def __add__(self, other: object) -> object: ...
+    def __radd__(self, other: object) -> object: ...
+    def __sub__(self, other: object) -> object: ...
+    def __rsub__(self, other: object) -> object: ...
+    def __mul__(self, other: object) -> object: ...
+    def __rmul__(self, other: object) -> object: ...
+    def __truediv__(self, other: object) -> object: ...
+    def __rtruediv__(self, other: object) -> object: ...
+    def __mod__(self, other: object) -> object: ...
+    def __rmod__(self, other: object) -> object: ...
+    def __pow__(self, other: object, modulo: None = None) -> object: ...
+    def __rpow__(self, other: object) -> object: ...
+    def __neg__(self) -> object: ...
+    def __lt__(self, other: object) -> bool: ...
+    def __le__(self, other: object) -> bool: ...
+    def __gt__(self, other: object) -> bool: ...
+    def __ge__(self, other: object) -> bool: ...
+    def __bool__(self) -> bool: ...
+    def __int__(self) -> int: ...
+    def __float__(self) -> float: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
 
-class VMString:
+class VMString(VMValue):
     def __init__(self, gc: GCSystem, value: str) -> None: ...
     def get_value(self) -> str: ...
     def set_value(self, value: str) -> None: ...
     def __len__(self) -> int: ...
+    def byte_len(self) -> int: ...
+    def __getitem__(self, index: int | slice) -> VMString: ...
+    def __contains__(self, item: object) -> bool: ...
+    def __iter__(self) -> Iterator[VMString]: ...
+    def encode(self, encoding: str = "utf-8", errors: str = "strict") -> VMBytes: ...
+    @staticmethod
+    def from_bytes(
+        b: bytes | VMBytes,
+        encoding: str = "utf-8",
+        errors: str = "strict",
+        gc: Optional[GCSystem] = None,
+    ) -> VMString: ...
+    def __add__(self, other: object) -> object: ...
+    def __radd__(self, other: object) -> object: ...
+    def __bool__(self) -> bool: ...
+    def __mod__(self, other: object) -> object: ...
+    def __rmod__(self, other: object) -> object: ...
+    def __int__(self) -> int: ...
+    def __float__(self) -> float: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
 
-class VMNull:
+class VMNull(VMValue):
     def __init__(self, gc: GCSystem) -> None: ...
+    def __bool__(self) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
 
-class VMBytes:
-    def __init__(self, gc: GCSystem, value: bytes) -> None: ...
+class VMBytes(VMValue):
+    def __init__(self, gc: GCSystem, value: bytes | bytearray | memoryview) -> None: ...
     def get_value(self) -> bytes: ...
-    def set_value(self, value: bytes) -> None: ...
+    def set_value(self, value: bytes | bytearray | memoryview) -> None: ...
+    def __buffer__(self, flags: int) -> memoryview:
+        """A writable view sharing memory with the bytes. While it is open the
+        bytes cannot be resized; if a script assigns a value of another
+        length, the view keeps the old data until it is released."""
+    @overload
+    def __getitem__(self, index: int) -> int: ...
+    @overload
+    def __getitem__(self, index: slice) -> VMBytes: ...
+    def __setitem__(self, index: int | slice, value: object) -> None: ...
+    def __iter__(self) -> Iterator[int]: ...
+    def extend(self, values: object) -> None: ...
+    def append(self, byte: int) -> None: ...
+    def truncate(self, size: int) -> None: ...
+    def hex(self) -> str: ...
+    @staticmethod
+    def fromhex(string: str, gc: Optional[GCSystem] = None) -> VMBytes: ...
+    def find(
+        self, sub: int | bytes, start: Optional[int] = None, end: Optional[int] = None
+    ) -> int: ...
+    def startswith(self, prefix: bytes) -> bool: ...
     def __len__(self) -> int: ...
+    def __add__(self, other: object) -> object: ...
+    def __radd__(self, other: object) -> object: ...
+    def __bool__(self) -> bool: ...
+    def __contains__(self, item: object) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    __hash__: ClassVar[None]  # type: ignore[assignment]
 
-class VMKeyVal:
+class VMKeyVal(VMValue):
     def __init__(self, gc: GCSystem, key: object, value: object) -> None: ...
     def get_key(self) -> object: ...
     def set_key(self, key: object) -> None: ...
     def get_value(self) -> object: ...
     def set_value(self, value: object) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
 
-class VMNamed:
+class VMNamed(VMValue):
     def __init__(self, gc: GCSystem, name: object, value: object) -> None: ...
     def get_name(self) -> object: ...
     def set_name(self, name: object) -> None: ...
     def get_value(self) -> object: ...
     def set_value(self, value: object) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
 
-class VMTuple:
+class VMTuple(VMValue):
     def __init__(self, gc: GCSystem, values: list) -> None: ...
     def to_list(self) -> list: ...
     def __getitem__(self, index: int) -> object: ...
     def __getattr__(self, name): ...
     def __len__(self) -> int: ...
+    def __add__(self, other: object) -> object: ...
+    def __radd__(self, other: object) -> object: ...
+    def __bool__(self) -> bool: ...
+    def __contains__(self, item: object) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
 
-class VMWrapper:
+class VMWrapper(VMValue):
     def __init__(self, gc: GCSystem, value: object) -> None: ...
     def get_value(self) -> object: ...
     def set_value(self, value: object) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
 
-class VMRange:
+class VMRange(VMValue):
     def __init__(self, gc: GCSystem, start: int, end: int) -> None: ...
     def get_start(self) -> int: ...
     def get_key(self) -> int: ...
     def get_end(self) -> int: ...
     def get_value(self) -> int: ...
     def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[int]: ...
+    def __reversed__(self) -> Iterator[int]: ...
+    def __contains__(self, item: object) -> bool: ...
+    @overload
+    def __getitem__(self, index: int) -> int: ...
+    @overload
+    def __getitem__(self, index: slice) -> VMRange: ...
+    def to_py(self) -> range: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+
+class VMBoolean(VMValue):
+    def __init__(self, gc: GCSystem, value: bool) -> None: ...
+    def get_value(self) -> bool: ...
+    def set_value(self, value: bool) -> None: ...
+    def clone(self) -> VMBoolean: ...
+    def to_py(self) -> bool: ...
+    def __and__(self, other: object) -> VMBoolean: ...
+    def __rand__(self, other: object) -> VMBoolean: ...
+    def __or__(self, other: object) -> VMBoolean: ...
+    def __ror__(self, other: object) -> VMBoolean: ...
+    def __invert__(self) -> VMBoolean: ...
+    def __bool__(self) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+
+class VMSet(VMValue):
+    def get_collection(self) -> object: ...
+    def get_filter(self) -> object: ...
+    def __contains__(self, item: object) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+
+class VMInstructions(VMValue):
+    def signatures(self) -> list[str]: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
 
-class Lambda:
+class VMCLambdaInstruction(VMValue):
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+
+class VMObject(VMValue):
+    def get_value(self) -> object: ...
+    def __getattr__(self, name: str) -> object: ...
+    def __getitem__(self, index: object) -> object: ...
+    def __len__(self) -> int: ...
+
+class Lambda(VMValue):
     def __init__(self, gc: GCSystem) -> None: ...
     def load(
         self,
@@ -96,17 +391,33 @@ class Lambda:
     def __call__(self, **kwargs: any) -> any: ...
     @overload
     def __call__(self, *args: any, **kwargs: any) -> any: ...
-    def __call__(self, *args: any, **kwargs: any) -> any: ...
+    def __call__(self, *args: any, **kwargs: any) -> any:
+        """Wrapped Python callbacks and the run condition may allocate values,
+        read the heap and call other Lambdas. Calling a Lambda that is already
+        running raises XlangExecutionError."""
+        ...
+    def set_run_condition(self, run_condition: Optional[callable] = None) -> None: ...
     def __repr__(self) -> str: ...
 
-class WrappedPyFunction:
+class WrappedPyFunction(VMValue):
     def __init__(self, gc: GCSystem) -> None: ...
     def wrap(self, py_callable: callable, default_args: VMTuple) -> None: ...
     def __repr__(self) -> str: ...
 
 def wrap_py_function(gc: GCSystem, func: callable) -> WrappedPyFunction: ...
+def debug_borrows(enabled: bool = True) -> bool:
+    """Records where each heap borrow is taken so that XlangBorrowError names
+    the holder. Returns the previous setting."""
+    ...
+def default_gc() -> GCSystem: ...
+def set_default_gc(gc: Optional[GCSystem] = None) -> Optional[GCSystem]: ...
 
 class XlangSetupError: ...
 class XlangCompilationError: ...
 class XlangTranslationError: ...
 class XlangExecutionError: ...
+class XlangLeakError: ...
+class CrossHeapError(ValueError):
+    """Raised when a value from one GCSystem is passed to another."""
+class XlangBorrowError(RuntimeError):
+    """Raised when the GCSystem is already borrowed by another operation."""
@@ -1,8 +1,11 @@
+import gc as pygc
+import os
+import pickle
 import sys
 import unittest
-import os
+import weakref
 
-from xlang import GCSystem, VMTuple, wrap_py_function
+from xlang import CrossHeapError, GCSystem, VMString, VMTuple, VMValue, XlangBorrowError, XlangExecutionError, XlangLeakError, debug_borrows, set_default_gc, wrap_py_function
 
 class TestXLang(unittest.TestCase):
     def setUp(self):
@@ -167,6 +170,919 @@ class TestXLang(unittest.TestCase):
 
         print(xlang_lambda(kwargs={"print": wrapped_func}).get_value())
 
+    def test_operators(self):
+        """测试运算符重载遵循 xlang 语义"""
+        a = self.gc.new_int(7)
+        b = self.gc.new_int(2)
+
+        self.assertEqual((a + b).get_value(), 9)
+        self.assertEqual((a - 3).get_value(), 4)
+        self.assertEqual((10 - a).get_value(), 3)
+        self.assertEqual((a * a).get_value(), 49)
+        self.assertEqual((a % b).get_value(), 1)
+        self.assertEqual((a ** b).get_value(), 49)
+        self.assertEqual((a << 1).get_value(), 14)
+        self.assertEqual((-a).get_value(), -7)
+        self.assertAlmostEqual((self.gc.new_float(1.5) * 2).get_value(), 3.0)
+        self.assertEqual((self.gc.new_string("ab") + "cd").get_value(), "abcd")
+        self.assertEqual(len((self.gc.new_tuple([1]) + self.gc.new_tuple([2])).to_list()), 2)
+
+        self.assertTrue(b < a)
+        self.assertTrue(a >= 7)
+        self.assertFalse(a <= b)
+        self.assertTrue(bool(a))
+        self.assertFalse(bool(self.gc.new_null()))
+        self.assertEqual(int(self.gc.new_float(2.9)), 2)
+        self.assertEqual([0, 1, 2][b], 2)
+
+        with self.assertRaises(ZeroDivisionError):
+            a % 0
+        with self.assertRaises(TypeError):
+            a + object()
+
+        # 整数溢出抛出 OverflowError，而不是让虚拟机 panic 或静默回绕
+        self.gc.collect()
+        before = self.gc.object_count()
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(2**63 - 1) + 1
+        with self.assertRaises(OverflowError):
+            -2**63 - self.gc.new_int(1)
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(2**62) * 4
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(1) << 100
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(3) << 62
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(-2**63) % -1
+        with self.assertRaises(OverflowError):
+            self.gc.new_int(10) ** 30
+        with self.assertRaises(OverflowError) as cm:
+            -self.gc.new_int(-2**63)
+        self.assertEqual(str(cm.exception), "Overflow when negating: -9223372036854775808")
+        with self.assertRaises(TypeError) as cm:
+            self.gc.new_int(1) + "a"
+        self.assertFalse(str(cm.exception).startswith("ValueError"))
+        with self.assertRaises(ValueError):
+            a << -1
+        with self.assertRaises(ValueError):
+            a ** -1
+        with self.assertRaises(TypeError):
+            pow(a, 2, 5)
+        with self.assertRaises(TypeError):
+            pow(self.gc.new_float(2.0), 2, 5)
+        self.assertEqual((self.gc.new_int(0) << 100).get_value(), 0)
+        self.assertEqual((self.gc.new_int(-8) >> 100).get_value(), -1)
+        self.gc.collect()
+        self.assertEqual(self.gc.object_count(), before)
+
+    def test_equality_and_hash(self):
+        """测试值相等、哈希与对象同一性"""
+        a = self.gc.new_string("a")
+        b = self.gc.new_string("a")
+        self.assertEqual(a, b)
+        self.assertEqual(a, "a")
+        self.assertNotEqual(a, self.gc.new_string("b"))
+        self.assertEqual(self.gc.new_int(1), self.gc.new_float(1.0))
+        self.assertEqual(len({a, b, "a"}), 1)
+        self.assertEqual(hash(self.gc.new_int(1)), hash(1.0))
+        # VMBytes 可以原地修改，与 bytearray 一样不可哈希
+        with self.assertRaises(TypeError):
+            hash(self.gc.new_bytes(b"k"))
+
+        self.assertFalse(a.same_object(b))
+        self.assertTrue(a.same_object(a))
+        self.assertNotEqual(a.id(), b.id())
+
+        t = self.gc.new_tuple([1, 2])
+        self.assertEqual(t, self.gc.new_tuple([1, 2]))
+        with self.assertRaises(TypeError):
+            hash(t)
+        self.assertTrue(t[0].same_object(t[0]))
+
+    def test_string_unicode(self):
+        """测试字符串按字符而非字节处理"""
+        s = self.gc.new_string("你好, xlang")
+        self.assertEqual(len(s), 9)
+        self.assertEqual(s.byte_len(), 13)
+        self.assertEqual(s[0], "你")
+        self.assertEqual(s[-1], "g")
+        self.assertEqual(s[0:2], "你好")
+        self.assertEqual(s[::-1].get_value(), "gnalx ,好你")
+        self.assertIn("好", s)
+        self.assertNotIn("世", s)
+        self.assertEqual("".join(c.get_value() for c in s), "你好, xlang")
+        self.assertEqual((s + "!").get_value(), "你好, xlang!")
+        with self.assertRaises(IndexError):
+            s[9]
+
+        encoded = s.encode()
+        self.assertEqual(encoded.get_value(), "你好, xlang".encode())
+        self.assertEqual(VMString.from_bytes(encoded).get_value(), "你好, xlang")
+        gbk = VMString.from_bytes("你好".encode("gbk"), "gbk", gc=self.gc)
+        self.assertEqual(gbk.get_value(), "你好")
+        self.assertEqual(VMString.from_bytes(b"\xff", errors="replace", gc=self.gc), "\ufffd")
+        # 未提供 gc 时解码到默认堆
+        self.assertEqual(VMString.from_bytes(b"plain").get_value(), "plain")
+
+    def test_bytes_buffer(self):
+        """测试 VMBytes 的 buffer 协议与零拷贝视图"""
+        b = self.gc.new_bytes(b"hello")
+        view = memoryview(b)
+        self.assertEqual(view.tobytes(), b"hello")
+        self.assertEqual(bytes(b), b"hello")
+
+        # 视图与虚拟机中的字节共享内存
+        view[0] = ord("j")
+        self.assertEqual(b.get_value(), b"jello")
+        b[0] = ord("x")
+        self.assertEqual(view.tobytes(), b"xello")
+
+        # 存在导出时不能替换底层存储
+        with self.assertRaises(BufferError):
+            b.set_value(b"other")
+
+        # 脚本重新赋值时长度不变，视图能看到新内容
+        script = self.gc.new_lambda()
+        script.load(
+            code="""
+                @required B;
+                B = $"d29ybGQ=";
+                B
+                """,
+            default_args=self.gc.new_tuple([]),
+        )
+        script(kwargs={"B": b})
+        self.assertEqual(b.get_value(), b"world")
+        self.assertEqual(view.tobytes(), b"world")
+        view[0] = ord("W")
+        self.assertEqual(b.get_value(), b"World")
+
+        # 脚本改变长度时虚拟机换用新存储，视图指向的内存在释放前保持有效
+        script = self.gc.new_lambda()
+        script.load(
+            code="""
+                @required B;
+                B = $"YSBtdWNoIGxvbmdlciBwYXlsb2FkIHRoYXQgZm9yY2VzIGEgcmVhbGxvY2F0aW9u";
+                B
+                """,
+            default_args=self.gc.new_tuple([]),
+        )
+        script(kwargs={"B": b})
+        self.assertEqual(b.get_value(), b"a much longer payload that forces a reallocation")
+        self.assertEqual(view.tobytes(), b"World")
+        self.assertEqual(bytes(memoryview(b)[:6]), b"a much")
+        view.release()
+        b.set_value(b"other")
+        self.assertEqual(b.get_value(), b"other")
+
+        import array
+        self.assertEqual(self.gc.new_bytes(memoryview(b"abc")[1:]).get_value(), b"bc")
+        self.assertEqual(self.gc.new_bytes(bytearray(b"xy")).get_value(), b"xy")
+        self.assertEqual(len(self.gc.new_bytes(array.array("i", [1, 2]))), 2 * array.array("i").itemsize)
+        with self.assertRaises(TypeError):
+            self.gc.new_bytes("not bytes")
+
+    def test_bytes_mutation(self):
+        """测试 VMBytes 的原地修改接口"""
+        b = self.gc.new_bytes(b"hello")
+        holder = self.gc.new_tuple([b])
+        self.assertEqual(b[0], ord("h"))
+        self.assertEqual(b[-1], ord("o"))
+        self.assertEqual(b[1:3].get_value(), b"el")
+
+        b[0] = ord("j")
+        b[1:3] = b"EE"
+        b[3:] = b"ly!"
+        self.assertEqual(b.get_value(), b"jEEly!")
+        b[::2] = [0x61, 0x62, 0x63]
+        self.assertEqual(b.get_value(), b"aEblc!")
+        b.append(0x21)
+        b.extend(b"??")
+        b.truncate(4)
+        self.assertEqual(list(b), [0x61, 0x45, 0x62, 0x6C])
+        # 脚本持有的引用看到同一份数据
+        self.assertEqual(holder[0].get_value(), b"aEbl")
+
+        self.assertEqual(b.hex(), "6145626c")
+        self.assertEqual(type(b).fromhex("de ad", gc=self.gc).get_value(), b"\xde\xad")
+        self.assertEqual(type(b).fromhex("0a").get_value(), b"\n")
+        self.assertEqual(b.find(b"b"), 2)
+        self.assertEqual(b.find(0x61, 1), -1)
+        self.assertTrue(b.startswith(b"aE"))
+
+        # 参数就是自身时先复制数据，不会因重复借用而失败
+        self.assertEqual(b.find(b), 0)
+        self.assertTrue(b.startswith(b))
+        b[0:2] = b
+        self.assertEqual(b.get_value(), b"aEblbl")
+        b.extend(b)
+        self.assertEqual(b.get_value(), b"aEblblaEblbl")
+        b.set_value(b)
+        b[::2] = b[1::2]
+        self.assertEqual(b.get_value(), b"EEllllEEllll")
+        b.set_value(b"aEbl")
+
+        with self.assertRaises(ValueError):
+            b[0] = 256
+        with self.assertRaises(IndexError):
+            b[10] = 0
+        view = memoryview(b)
+        with self.assertRaises(BufferError):
+            b.append(0)
+        with self.assertRaises(BufferError):
+            b.extend(b"xy")
+        with self.assertRaises(BufferError):
+            b.truncate(1)
+        with self.assertRaises(BufferError):
+            b[0:1] = b"xy"
+        b[0:1] = b"y"
+        b[0] = ord("z")
+        self.assertEqual(view[0], ord("z"))
+        view.release()
+
+    def test_range(self):
+        """测试 VMRange 的迭代、成员判断与索引"""
+        r = self.gc.new_range(2, 6)
+        self.assertEqual(list(r), [2, 3, 4, 5])
+        self.assertEqual(list(reversed(r)), [5, 4, 3, 2])
+        self.assertEqual(len(r), 4)
+        self.assertIn(2, r)
+        self.assertIn(self.gc.new_int(5), r)
+        self.assertNotIn(6, r)
+        self.assertNotIn("2", r)
+        self.assertIn(3.0, r)
+        self.assertIn(self.gc.new_float(5.0), r)
+        self.assertNotIn(3.5, r)
+        self.assertNotIn(float("nan"), r)
+        self.assertNotIn(float("inf"), r)
+        self.assertIn(True, self.gc.new_range(0, 2))
+        self.assertNotIn(2**70, r)
+
+        class Equal:
+            def __eq__(self, other):
+                return other == 4
+
+        self.assertIn(Equal(), r)
+        self.assertEqual(r[0], 2)
+        self.assertEqual(r[-1], 5)
+        self.assertEqual(r[1:3].to_py(), range(3, 5))
+        self.assertEqual(hash(self.gc.new_range(0, 3)), hash(range(0, 3)))
+        self.assertEqual(len({self.gc.new_range(0, 3), range(0, 3)}), 1)
+        with self.assertRaises(IndexError):
+            r[4]
+
+        # 反向区间视为空区间
+        empty = self.gc.new_range(5, 2)
+        self.assertEqual(len(empty), 0)
+        self.assertEqual(list(empty), [])
+        self.assertFalse(bool(empty))
+        self.assertNotIn(3, empty)
+
+        # 长度超出 i64 时与 Python range 一样抛出 OverflowError
+        huge = self.gc.new_range(-2**63, 2**63 - 1)
+        with self.assertRaises(OverflowError):
+            len(huge)
+        with self.assertRaises(OverflowError):
+            huge[0]
+        self.assertEqual(self.gc.new_range(2**63 - 3, 2**63 - 1)[-1], 2**63 - 2)
+
+        # Python range 会被转换为 VMRange
+        t = self.gc.new_tuple([range(1, 4)])
+        self.assertEqual(repr(t[0]), "VMRange(1, 4)")
+        with self.assertRaises(ValueError):
+            self.gc.new_tuple([range(0, 10, 2)])
+
+    def test_returned_closure(self):
+        """测试脚本返回的闭包可以在 Python 中直接调用"""
+        calls = []
+
+        def condition():
+            calls.append(1)
+
+        xlang_lambda = self.gc.new_lambda()
+        xlang_lambda.load(
+            code="""
+                @required k;
+                (x => 0) -> &k x + k
+                """,
+            default_args=self.gc.new_tuple([]),
+            run_condition=condition,
+        )
+        add_k = xlang_lambda(kwargs={"k": 10})
+        self.assertTrue(callable(add_k))
+        calls.clear()
+        self.assertEqual(add_k(kwargs={"x": 5}).get_value(), 15)
+        # 返回的闭包继承 run_condition
+        self.assertGreater(len(calls), 0)
+
+        add_k.set_run_condition(None)
+        calls.clear()
+        self.assertEqual(add_k(kwargs={"x": 1}).get_value(), 11)
+        self.assertEqual(len(calls), 0)
+
+    def test_lambda_as_value(self):
+        """测试将 Lambda 作为参数传入其他脚本"""
+        double = self.gc.new_lambda()
+        double.load(code="@required x; x * 2", default_args=self.gc.new_tuple([]))
+
+        main = self.gc.new_lambda()
+        main.load(
+            code="@required f; f(x => 3) + f(x => 4)",
+            default_args=self.gc.new_tuple([]),
+        )
+        self.assertEqual(main(kwargs={"f": double}).get_value(), 14)
+        self.assertEqual(double(kwargs={"x": 10}).get_value(), 20)
+
+        # 放入元组后取出的是同一个可调用的 lambda
+        library = self.gc.new_tuple([double])
+        self.assertEqual(library[0](kwargs={"x": 1}).get_value(), 2)
+
+    def test_remaining_vm_types(self):
+        """测试布尔值、集合等类型有专门的包装类"""
+        def run(code):
+            xlang_lambda = self.gc.new_lambda()
+            xlang_lambda.load(code=code, default_args=self.gc.new_tuple([]))
+            return xlang_lambda()
+
+        flag = run("1 == 1")
+        self.assertEqual(type(flag).__name__, "VMBoolean")
+        self.assertIs(flag.to_py(), True)
+        self.assertTrue(flag)
+        self.assertEqual(flag, True)
+        self.assertFalse((flag & self.gc.new_boolean(False)).get_value())
+
+        # Python 的 bool 转换为 VMBoolean 而不是 VMInt
+        self.assertEqual(type(self.gc.new_tuple([True])[0]).__name__, "VMBoolean")
+
+        vm_set = run("(1, 2, 3) | (x => 0) -> x > 1")
+        self.assertEqual(type(vm_set).__name__, "VMSet")
+        self.assertEqual(vm_set.get_collection().to_py()[0].get_value(), 1)
+        self.assertTrue(callable(vm_set.get_filter()))
+
+    def test_value_base_class(self):
+        """测试所有包装类共享的 VMValue 接口"""
+        value = self.gc.new_int(3)
+        vm_tuple = self.gc.new_tuple([value, "x"])
+        xlang_lambda = self.gc.new_lambda()
+        for item in (value, vm_tuple, xlang_lambda, self.gc.new_boolean(True)):
+            self.assertIsInstance(item, VMValue)
+
+        self.assertEqual(value.type_name, "int")
+        self.assertEqual(vm_tuple.type_name, "tuple")
+        # 一个来自 Python 包装对象，一个来自元组
+        self.assertEqual(value.refcount, 2)
+        self.assertEqual(value.gc.object_count(), self.gc.object_count())
+
+        shallow = VMValue.clone(vm_tuple)
+        deep = vm_tuple.deepcopy()
+        self.assertTrue(shallow[0].same_object(value))
+        self.assertFalse(deep[0].same_object(value))
+        self.assertEqual(deep.to_py()[1].get_value(), "x")
+
+        # 未加载的 Lambda 没有对应的堆对象
+        with self.assertRaises(TypeError):
+            xlang_lambda.type_name
+        xlang_lambda.load(code="1 + 2", default_args=self.gc.new_tuple([]))
+        self.assertEqual(xlang_lambda.type_name, "lambda")
+
+        # Python 子类可以包装已有的 VM 值或新转换的 Python 值
+        class Tagged(VMValue):
+            tag = "custom"
+
+        self.gc.collect()
+        before = self.gc.object_count()
+        refcount = value.refcount
+        shared = Tagged(self.gc, value)
+        self.assertIsInstance(shared, VMValue)
+        self.assertEqual(shared.tag, "custom")
+        self.assertTrue(shared.same_object(value))
+        self.assertEqual(value.refcount, refcount + 1)
+        converted = Tagged(self.gc, "hello")
+        self.assertEqual(converted.type_name, "string")
+        self.assertEqual(self.gc.new_tuple([converted])[0].get_value(), "hello")
+        with self.assertRaises(CrossHeapError):
+            Tagged(GCSystem(), value)
+        del shared, converted
+        self.gc.collect()
+        self.assertEqual(self.gc.object_count(), before)
+
+    def test_pickle(self):
+        """测试值图的 pickle 往返，反序列化到默认 GCSystem"""
+        shared = self.gc.new_string("shared")
+        vm_tuple = self.gc.new_tuple(
+            [1, 2.5, shared, shared, self.gc.new_keyval("k", None), b"raw", True, range(1, 4)]
+        )
+        data = pickle.dumps(vm_tuple)
+
+        target = GCSystem()
+        previous = set_default_gc(target)
+        try:
+            restored = pickle.loads(data)
+        finally:
+            set_default_gc(previous)
+        self.assertEqual(restored.gc.object_count(), target.object_count())
+        self.assertEqual(restored[0].get_value(), 1)
+        self.assertEqual(restored[4].get_key().get_value(), "k")
+        self.assertEqual(restored[5].get_value(), b"raw")
+        self.assertIs(restored[6].get_value(), True)
+        # 共享的子值在重建后依然共享
+        self.assertTrue(restored[2].same_object(restored[3]))
+
+        xlang_lambda = self.gc.new_lambda()
+        xlang_lambda.load(code="1", default_args=self.gc.new_tuple([]))
+        with self.assertRaises(pickle.PicklingError):
+            pickle.dumps(xlang_lambda)
+
+        del restored
+        target.collect()
+        self.assertEqual(target.object_count(), 0)
+
+    def test_json(self):
+        """测试 JSON 导入导出，bytes 使用 $bytes/base64 约定"""
+        text = '{"b": 1, "a": [true, null, 2.5, "s", {"$bytes": "aGk="}]}'
+        value = self.gc.from_json(text)
+        self.assertEqual(value.type_name, "tuple")
+        self.assertEqual(value[0].get_key().get_value(), "b")
+        items = value[1].get_value()
+        self.assertIs(items[0].get_value(), True)
+        self.assertEqual(items[4].get_value(), b"hi")
+
+        self.assertEqual(
+            value.to_json(),
+            '{"b":1,"a":[true,null,2.5,"s",{"$bytes":"aGk="}]}',
+        )
+        self.assertTrue(value.to_json(indent=2, sort_keys=True).startswith('{\n  "a": ['))
+
+        named = self.gc.from_json(b'{"x": 1}', object_pairs="named")
+        self.assertEqual(type(named[0]).__name__, "VMNamed")
+        self.assertEqual(named.to_json(), '{"x":1}')
+
+        with self.assertRaises(ValueError):
+            self.gc.from_json("{")
+        with self.assertRaises(TypeError):
+            self.gc.new_range(0, 3).to_json()
+
+    def test_literal_round_trip(self):
+        """测试 to_source 输出的字面量可以被 parse_literal 和脚本读回"""
+        text = '(1, -2, 2.5, "a\\"b\\n", true, null, $"aGk=", (-1)..2, "k" : (1 : 2), "x" => (-1), wrap (1,), A::B::(1 : 2))'
+        value = self.gc.parse_literal(text)
+        self.assertEqual(value.to_source(), text)
+        self.assertEqual(value[3].get_value(), 'a"b\n')
+        self.assertEqual(value[6].get_value(), b"hi")
+
+        xlang_lambda = self.gc.new_lambda()
+        xlang_lambda.load(code=text, default_args=self.gc.new_tuple([]))
+        self.assertEqual(xlang_lambda().to_source(), text)
+
+        # 只接受字面量，不执行任何代码
+        for code in ["f(1)", "x", "1 + 2"]:
+            with self.assertRaises(ValueError):
+                self.gc.parse_literal(code)
+        with self.assertRaises(TypeError):
+            xlang_lambda.to_source()
+
+    def test_gc_stats(self):
+        """测试 stats 的按类型统计以及 collect 返回的释放数量"""
+        gc = GCSystem()
+        stats = gc.stats()
+        self.assertEqual(stats["live_objects"], 0)
+        self.assertEqual(stats["collections"], 0)
+
+        text = gc.new_string("x" * 1000)
+        values = gc.new_tuple([1, 2.5, "a"])
+        stats = gc.stats()
+        self.assertEqual(stats["live_objects"], gc.object_count())
+        self.assertEqual(stats["by_type"]["string"]["count"], 2)
+        self.assertEqual(stats["by_type"]["tuple"]["count"], 1)
+        self.assertGreater(stats["by_type"]["string"]["bytes"], 1000)
+        self.assertEqual(
+            stats["approx_bytes"],
+            sum(entry["bytes"] for entry in stats["by_type"].values()),
+        )
+        self.assertGreater(sys.getsizeof(text), 1000)
+
+        del values
+        self.assertEqual(gc.collect(), 4)
+        # 通过值取得的 GCSystem 共享同一份统计
+        stats = text.gc.stats()
+        self.assertEqual(stats["collections"], 1)
+        self.assertEqual(stats["last_freed"], 4)
+        self.assertGreaterEqual(stats["collect_time"], 0.0)
+
+        del text
+        self.assertEqual(gc.collect(), 1)
+        stats = gc.stats()
+        self.assertEqual(stats["total_freed"], 5)
+        self.assertEqual(stats["by_type"], {})
+
+    def test_heap_inspection(self):
+        """测试 live_objects、referrers、referents 以及快照对比"""
+        gc = GCSystem()
+        before = gc.snapshot()
+        item = gc.new_string("item")
+        values = gc.new_tuple([item, 1])
+        after = gc.snapshot()
+        self.assertEqual(len(before), 0)
+        self.assertIn(item, after)
+
+        appeared = before.diff(after)
+        self.assertEqual(len(appeared), 3)
+        self.assertTrue(any(value.same_object(values) for value in appeared))
+        self.assertEqual(after.diff(before), [])
+
+        strings = gc.live_objects("string")
+        self.assertEqual([value.get_value() for value in strings], ["item"])
+        self.assertEqual(len(gc.live_objects(VMTuple)), 1)
+        self.assertEqual(len(gc.live_objects()), 3)
+
+        referrers = gc.referrers(item)
+        self.assertEqual(len(referrers), 1)
+        self.assertTrue(referrers[0].same_object(values))
+        # item、strings 和 appeared 中的三个包装对象加上元组持有的引用
+        self.assertEqual(item.refcount, 4)
+        referents = gc.referents(values)
+        self.assertEqual(sorted(value.type_name for value in referents), ["int", "string"])
+
+        del appeared, strings, referrers, referents, values, item
+        gc.collect()
+        self.assertEqual(before.diff(after), [])
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_gc_policy(self):
+        """测试自动回收策略以及按触发原因统计"""
+        gc = GCSystem()
+        self.assertEqual(gc.get_policy()["collect_after_call"], False)
+        xlang_lambda = gc.new_lambda()
+        xlang_lambda.load(
+            code="i := 0; while (i < 200) { t := (i, i); i = i + 1 }; i",
+            default_args=gc.new_tuple([]),
+        )
+
+        gc.set_policy(allocation_threshold=None, step_interval=50)
+        self.assertEqual(xlang_lambda().get_value(), 200)
+        stats = gc.stats()
+        self.assertGreater(stats["by_trigger"]["step"], 0)
+        self.assertEqual(stats["last_trigger"], "step")
+
+        gc.set_policy(allocation_threshold=100)
+        xlang_lambda()
+        self.assertGreater(gc.stats()["by_trigger"]["allocation"], 0)
+
+        gc.set_policy(allocation_threshold=None, collect_after_call=True)
+        with gc.paused():
+            self.assertFalse(gc.get_policy()["enabled"])
+            xlang_lambda()
+        self.assertEqual(gc.stats()["by_trigger"]["call"], 0)
+        xlang_lambda()
+        self.assertEqual(gc.stats()["by_trigger"]["call"], 1)
+        self.assertEqual(gc.stats()["last_trigger"], "call")
+
+        gc.collect()
+        self.assertEqual(gc.stats()["by_trigger"]["manual"], 1)
+        with self.assertRaises(ValueError):
+            gc.set_policy(step_interval=0)
+
+        del xlang_lambda
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_leak_check(self):
+        """测试 leak_check 报告新增的存活对象以及 GCSystem 释放时的泄漏警告"""
+        gc = GCSystem()
+        kept = gc.new_string("before")
+        with gc.leak_check():
+            temporary = gc.new_tuple([1, 2])
+            del temporary
+
+        with self.assertRaises(XlangLeakError) as context:
+            with gc.leak_check():
+                leaked = gc.new_string("leaked")
+        message = str(context.exception)
+        self.assertIn("1 VM objects leaked: string x1", message)
+        self.assertIn("leaked", message)
+        self.assertNotIn("before", message)
+
+        # 代码块抛出异常时不做检查，原异常照常传播
+        with self.assertRaises(KeyError):
+            with gc.leak_check():
+                gc.new_int(1)
+                raise KeyError("boom")
+
+        del kept, leaked
+        gc.collect()
+
+        gc = GCSystem(warn_on_leak=True)
+        self.assertTrue(gc.warn_on_leak)
+        value = gc.new_int(1)
+        with self.assertWarns(ResourceWarning) as warning:
+            del gc
+        self.assertIn("int x1", str(warning.warning))
+        del value
+
+    def test_scope(self):
+        """测试作用域释放、keep、装饰器以及显式 release"""
+        gc = GCSystem()
+        with gc.scope() as scope:
+            temporary = gc.new_string("temporary")
+            kept = scope.keep(gc.new_tuple([1, 2]))
+            with gc.scope() as inner:
+                # 嵌套作用域 keep 后由外层作用域持有
+                inner_kept = inner.keep(gc.new_int(3))
+                dropped = gc.new_int(4)
+            self.assertTrue(dropped.released)
+            self.assertEqual(inner_kept.get_value(), 3)
+        self.assertTrue(temporary.released)
+        self.assertTrue(inner_kept.released)
+        self.assertFalse(kept.released)
+        with self.assertRaises(ReferenceError):
+            temporary.get_value()
+        with self.assertRaises(ReferenceError):
+            repr(temporary)
+        self.assertEqual(len(kept), 2)
+
+        @gc.scope()
+        def build(n):
+            gc.new_string("scratch")
+            return gc.new_int(n), [gc.new_int(n + 1)]
+
+        value, [other] = build(5)
+        self.assertEqual(value.get_value(), 5)
+        self.assertEqual(other.get_value(), 6)
+
+        # 显式释放，重复调用无效果
+        kept.release()
+        kept.release()
+        self.assertTrue(kept.released)
+        value.release()
+        other.release()
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+        # 导出的缓冲区自己持有引用
+        b = gc.new_bytes(b"abc")
+        view = memoryview(b)
+        b.release()
+        gc.collect()
+        self.assertEqual(bytes(view), b"abc")
+        view.release()
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_weak_references(self):
+        """测试 weakref.ref 以及不持有对象的 gc.weak 句柄"""
+        gc = GCSystem()
+        value = gc.new_string("cached")
+        ref = weakref.ref(value)
+        self.assertIs(ref(), value)
+        cache = weakref.WeakKeyDictionary()
+        cache[value] = "metadata"
+        del value
+        self.assertIsNone(ref())
+        self.assertEqual(len(cache), 0)
+
+        value = gc.new_tuple([1, 2])
+        handle = gc.weak(value)
+        self.assertTrue(handle.alive)
+        self.assertTrue(handle().same_object(value))
+        # 句柄本身不持有引用
+        del value
+        self.assertTrue(handle.alive)
+        gc.collect()
+        self.assertFalse(handle.alive)
+        self.assertIsNone(handle())
+        self.assertIn("dead", repr(handle))
+        # 地址被新对象复用时句柄仍然失效
+        for _ in range(10):
+            gc.new_tuple([3, 4])
+        self.assertIsNone(handle())
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+        with self.assertRaises(ValueError):
+            GCSystem().weak(gc.new_int(1))
+
+    def test_python_reference_cycles(self):
+        """测试 Python 闭包与 xlang 堆之间的循环引用可以被 CPython 回收"""
+        def make_cycle():
+            gc = GCSystem()
+            value = gc.new_int(5)
+
+            def callback(x):
+                return gc.new_int(value.get_value() + x.get_value())
+
+            wrapped = wrap_py_function(gc, callback)
+            xlang_lambda = gc.new_lambda()
+            xlang_lambda.load(code="@required f; f(1)", default_args=gc.new_tuple([]))
+            self.assertEqual(xlang_lambda(kwargs={"f": wrapped}).get_value(), 6)
+            return weakref.ref(callback), weakref.ref(value)
+
+        callback_ref, value_ref = make_cycle()
+        pygc.collect()
+        self.assertIsNone(callback_ref())
+        self.assertIsNone(value_ref())
+
+        # 堆仍被引用时，丢弃包装对象后可调用对象仍然有效
+        gc = GCSystem()
+        wrapped = wrap_py_function(gc, lambda x: gc.new_int(x.get_value() * 2))
+        kwargs = {"f": wrapped}
+        del wrapped
+        pygc.collect()
+        xlang_lambda = gc.new_lambda()
+        xlang_lambda.load(code="@required f; f(21)", default_args=gc.new_tuple([]))
+        self.assertEqual(xlang_lambda(kwargs=kwargs).get_value(), 42)
+
+    def test_wrapped_function_lifetime(self):
+        """测试包装的 Python 可调用对象随 xlang 函数对象一同释放"""
+        gc = GCSystem()
+
+        def callback(x):
+            return x
+
+        xlang_lambda = gc.new_lambda()
+        xlang_lambda.load(code="@required f; f(1); f(2); f(3)", default_args=gc.new_tuple([]))
+        wrapped = wrap_py_function(gc, callback)
+        refcount = sys.getrefcount(callback)
+        # 多次调用不会泄漏引用
+        for _ in range(5):
+            self.assertEqual(xlang_lambda(kwargs={"f": wrapped}).get_value(), 3)
+        self.assertEqual(sys.getrefcount(callback), refcount)
+
+        # 包装对象释放后，脚本中仍持有的函数可以继续调用
+        captured = gc.new_lambda()
+        captured.load(code="@required f; () -> f(7)", default_args=gc.new_tuple([]))
+        closure = captured(kwargs={"f": wrapped})
+        del wrapped
+        gc.collect()
+        self.assertEqual(closure().get_value(), 7)
+
+        # 函数对象被回收时释放可调用对象，不需要 CPython 的循环回收
+        callback_ref = weakref.ref(callback)
+        del callback, closure, captured
+        gc.collect()
+        self.assertIsNone(callback_ref())
+        del xlang_lambda
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_cross_heap(self):
+        """测试不同 GCSystem 之间的值不能混用"""
+        gc_a = GCSystem()
+        gc_b = GCSystem()
+        foreign = gc_b.new_tuple([gc_b.new_int(1), gc_b.new_string("x")])
+
+        with self.assertRaises(CrossHeapError):
+            gc_a.new_tuple([gc_b.new_int(1)])
+        with self.assertRaises(CrossHeapError):
+            gc_a.new_tuple([1]) + foreign
+        with self.assertRaises(CrossHeapError):
+            gc_a.new_lambda().load(code="1", default_args=gc_b.new_tuple([]))
+        xlang_lambda = gc_a.new_lambda()
+        xlang_lambda.load(code="@required x; x", default_args=gc_a.new_tuple([]))
+        with self.assertRaises(CrossHeapError):
+            xlang_lambda(kwargs={"x": foreign})
+        # 只读比较可以跨堆
+        self.assertEqual(gc_a.new_int(1), gc_b.new_int(1))
+
+        count = gc_a.object_count()
+        adopted = gc_a.adopt(foreign)
+        self.assertEqual(gc_a.object_count(), count + 3)
+        self.assertEqual(adopted.to_py()[1].get_value(), "x")
+        self.assertEqual(xlang_lambda(kwargs={"x": adopted}).to_py()[0].get_value(), 1)
+        self.assertIs(gc_a.adopt(adopted), adopted)
+        foreign_lambda = gc_b.new_lambda()
+        foreign_lambda.load(code="1", default_args=gc_b.new_tuple([]))
+        with self.assertRaises(TypeError):
+            gc_a.adopt(foreign_lambda)
+
+        del foreign, adopted, xlang_lambda, foreign_lambda
+        gc_a.collect()
+        gc_b.collect()
+        self.assertEqual(gc_a.object_count(), 0)
+        self.assertEqual(gc_b.object_count(), 0)
+
+    def test_failures_roll_back(self):
+        """测试出错时抛出 Python 异常且不残留对象"""
+        gc = GCSystem()
+        self.assertTrue(issubclass(XlangBorrowError, RuntimeError))
+        xlang_lambda = gc.new_lambda()
+        xlang_lambda.load(code="@required x; x", default_args=gc.new_tuple([]))
+        baseline = gc.object_count()
+
+        with self.assertRaises(TypeError):
+            xlang_lambda(args=[gc.new_int(1)], kwargs={1: "x"})
+        with self.assertRaises(TypeError):
+            xlang_lambda(kwargs={"x": object()})
+        with self.assertRaises(TypeError):
+            gc.new_tuple(["a", ["b", object()]])
+        with self.assertRaises(TypeError):
+            gc.new_keyval("a", object())
+        gc.collect()
+        self.assertEqual(gc.object_count(), baseline)
+
+        # capture 和 self_object 由 lambda 持有，lambda 回收后一并释放
+        captured = gc.new_lambda()
+        captured.load(code="1", default_args=gc.new_tuple([]), capture=gc.new_int(1), self_object=gc.new_int(2))
+        del captured, xlang_lambda
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_reentrant_callbacks(self):
+        """测试回调中分配对象、读取堆、调用其他 lambda 以及推迟回收"""
+        gc = GCSystem()
+        inner = gc.new_lambda()
+        inner.load(code="@required y; y + 100", default_args=gc.new_tuple([]))
+        outer = gc.new_lambda()
+        seen = []
+
+        def callback(x):
+            value = gc.new_int(x.get_value() * 2)
+            seen.append(len(gc.live_objects()) > 0 and gc.object_count() > 0)
+            # 执行期间的回收推迟到最外层调用结束
+            self.assertEqual(gc.collect(), 0)
+            return inner(kwargs={"y": value})
+
+        def reenter(x):
+            return outer(kwargs={"f": wrapped})
+
+        between_steps = []
+
+        def condition():
+            gc.new_string("allocated between steps")
+            # 两步之间虚拟机不持有堆的借用
+            state = gc.borrow_state()
+            between_steps.append((state["shared"], state["mutable"], len(state["holders"])))
+
+        wrapped = wrap_py_function(gc, callback)
+        outer.load(
+            code="@required f; a := (1, 2); b := f(5); c := f(6); (a, b, c)",
+            default_args=gc.new_tuple([]),
+            run_condition=condition,
+        )
+        manual = gc.stats()["by_trigger"]["manual"]
+        self.assertFalse(debug_borrows(True))
+        try:
+            result = outer(kwargs={"f": wrapped})
+        finally:
+            self.assertTrue(debug_borrows(False))
+        self.assertEqual([v.get_value() for v in result.to_py()[1:]], [110, 112])
+        self.assertEqual(seen, [True, True])
+        self.assertTrue(between_steps)
+        self.assertEqual(set(between_steps), {(0, 0, 0)})
+        self.assertEqual(gc.stats()["by_trigger"]["manual"], manual + 1)
+
+        wrapped = wrap_py_function(gc, reenter)
+        with self.assertRaises(XlangExecutionError) as ctx:
+            outer(kwargs={"f": wrapped})
+        self.assertIn("re-entrantly", str(ctx.exception))
+
+        del inner, outer, wrapped, result
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
+    def test_borrow_diagnostics(self):
+        """测试借用冲突时报告持有者"""
+        gc = GCSystem()
+        errors = []
+        states = []
+
+        class Finalized(str):
+            def __del__(self):
+                # 回调结果在虚拟机重新持有堆之后才释放
+                try:
+                    gc.object_count()
+                except XlangBorrowError as e:
+                    errors.append(str(e))
+
+        def callback(x):
+            states.append(gc.borrow_state())
+            return [Finalized("x")]
+
+        xlang_lambda = gc.new_lambda()
+        xlang_lambda.load(code="@required f; f(1)", default_args=gc.new_tuple([]))
+        wrapped = wrap_py_function(gc, callback)
+        self.assertFalse(debug_borrows(True))
+        try:
+            xlang_lambda(kwargs={"f": wrapped})
+        finally:
+            self.assertTrue(debug_borrows(False))
+
+        # 回调期间执行中的 lambda 把可变借用借给了 Python 代码
+        holders = states[0]["holders"]
+        self.assertEqual(states[0]["mutable"], 0)
+        self.assertEqual([(h["kind"], h["lent"]) for h in holders], [("mutable", True)])
+        self.assertIn("xlang.rs", holders[0]["location"])
+        self.assertIn("test_borrow_diagnostics", holders[0]["python_stack"])
+        self.assertEqual(len(errors), 1)
+        self.assertIn("held by", errors[0])
+        self.assertIn("test_borrow_diagnostics", errors[0])
+
+        state = gc.borrow_state()
+        self.assertEqual((state["debug"], state["mutable"], state["holders"]), (False, 0, []))
+        del xlang_lambda, wrapped
+        gc.collect()
+        self.assertEqual(gc.object_count(), 0)
+
     def __del__(self):
         # 清理资源
         self.gc.collect()